@@ -1,22 +1,131 @@
-use crate::domains::auth_service::AuthService;
-use crate::domains::dto::auth::{LoginRequestDto, LogoutRequestDto, RegisterRequestDto};
+use crate::domains::auth_service::{AuthRepository, AuthService};
+use crate::domains::dto::auth::{
+    ChangePasswordRequestDto, LoginRequestDto, LogoutRequestDto, RegisterRequestDto,
+    ResetPasswordRequestDto, RevokeSessionsResponseDto,
+};
 use crate::errors::AppError;
+use crate::middlewares::auth_middleware::AuthMiddleware;
+use crate::models::user::{AuthenticatedUser, Role};
 use crate::repositories::auth_repository::AuthRepositoryImpl;
-use actix_web::{web, HttpResponse};
+use actix_web::dev::HttpServiceFactory;
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::warn;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// リクエスト元のIPアドレスの判定で信頼するプロキシのアドレス範囲
+///
+/// 接続元が信頼するプロキシの場合のみ、プロキシが設定する `X-Real-IP` ヘッダーを使う
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    networks: Vec<(IpAddr, u32)>,
+}
+
+impl TrustedProxies {
+    /// カンマ区切りのIPアドレスまたはCIDR表記（例: `172.16.0.0/12,127.0.0.1`）から作成する
+    ///
+    /// 解釈できない値はログに記録して無視する
+    pub fn parse(value: &str) -> Self {
+        let networks = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter_map(|entry| {
+                let network = parse_network(entry);
+                if network.is_none() {
+                    warn!("信頼するプロキシのアドレスを解釈できません: {}", entry);
+                }
+                network
+            })
+            .collect();
+        TrustedProxies { networks }
+    }
+
+    /// IPアドレスが信頼するプロキシのアドレス範囲に含まれるかどうかを返す
+    fn contains(&self, ip: IpAddr) -> bool {
+        self.networks
+            .iter()
+            .any(|&(network, prefix_len)| match (network, ip) {
+                (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                    let mask = u32::MAX.checked_shl(32 - prefix_len).unwrap_or(0);
+                    u32::from(network) & mask == u32::from(ip) & mask
+                }
+                (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                    let mask = u128::MAX.checked_shl(128 - prefix_len).unwrap_or(0);
+                    u128::from(network) & mask == u128::from(ip) & mask
+                }
+                _ => false,
+            })
+    }
+
+    /// リクエスト元のIPアドレスを返す
+    ///
+    /// 接続元が信頼するプロキシの場合は `X-Real-IP` ヘッダーの値を、
+    /// それ以外の場合やヘッダーがない場合は接続元のアドレスを返す
+    pub fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer_ip = req.peer_addr().map(|addr| addr.ip());
+        if !peer_ip.is_some_and(|peer_ip| self.contains(peer_ip)) {
+            return peer_ip;
+        }
+
+        req.headers()
+            .get("X-Real-IP")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|s| s.trim().parse().ok())
+            .or(peer_ip)
+    }
+}
+
+/// IPアドレスまたはCIDR表記をネットワークアドレスとプレフィックス長に変換する
+fn parse_network(entry: &str) -> Option<(IpAddr, u32)> {
+    let (address, prefix_len) = match entry.split_once('/') {
+        Some((address, prefix_len)) => (address, Some(prefix_len.parse::<u32>().ok()?)),
+        None => (entry, None),
+    };
+    let address: IpAddr = address.parse().ok()?;
+    let max_prefix_len = if address.is_ipv4() { 32 } else { 128 };
+    let prefix_len = prefix_len.unwrap_or(max_prefix_len);
+    (prefix_len <= max_prefix_len).then_some((address, prefix_len))
+}
 
 /// ユーザー登録を処理するハンドラー関数
 /// 
 /// `service` - ユーザー登録サービスのインスタンス
+/// `http_req` - HTTPリクエスト（管理者による登録の場合は Authorization ヘッダーを含む）
 /// `req` - 登録リクエストのデータ
 /// 
+/// Authorization ヘッダーを指定した場合、そのセッションのユーザーによる登録として扱い、
+/// セッションが無効な場合は HTTP 401 Unauthorized を返す
+/// 
 /// 成功した場合、HTTP 201 Created レスポンスを返す
 /// 失敗した場合、AppError を返す
-pub async fn register_handler(
-    service: web::Data<AuthService<AuthRepositoryImpl>>,
+pub async fn register_handler<T: AuthRepository + std::fmt::Debug + 'static>(
+    service: web::Data<AuthService<T>>,
+    http_req: HttpRequest,
     req: web::Json<RegisterRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    let caller = match http_req.headers().get("Authorization") {
+        Some(token) => {
+            let token = token.to_str().map_err(|_| AppError::Unauthorized)?;
+            match service.authenticate_session(token).await {
+                Ok(caller) => Some(caller),
+                Err(AppError::SqlxError(sqlx::Error::RowNotFound)) => {
+                    return Err(AppError::Unauthorized)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        None => None,
+    };
+
     match service
-        .register_user(&req.username, &req.password, &req.role, req.area_id)
+        .register_user(
+            &req.username,
+            &req.password,
+            &req.role,
+            req.area_id,
+            caller.as_ref(),
+        )
         .await
     {
         Ok(response) => Ok(HttpResponse::Created().json(response)),
@@ -27,15 +136,27 @@ pub async fn register_handler(
 /// ユーザーログインを処理するハンドラー関数
 /// 
 /// `service` - ユーザーログインサービスのインスタンス
+/// `trusted_proxies` - `X-Real-IP` ヘッダーを信頼するプロキシ
+/// `http_req` - HTTPリクエスト（リクエスト元のIPアドレスを取得する）
 /// `req` - ログインリクエストのデータ
 /// 
 /// 成功した場合、HTTP 200 OK レスポンスを返す
+/// ログインの試行が制限されている場合、HTTP 429 Too Many Requests を返す
 /// 失敗した場合、AppError を返す
 pub async fn login_handler(
     service: web::Data<AuthService<AuthRepositoryImpl>>,
+    trusted_proxies: web::Data<TrustedProxies>,
+    http_req: HttpRequest,
     req: web::Json<LoginRequestDto>,
 ) -> Result<HttpResponse, AppError> {
-    match service.login_user(&req.username, &req.password).await {
+    let remote_ip = trusted_proxies
+        .client_ip(&http_req)
+        .map(|ip| ip.to_string());
+
+    match service
+        .login_user(&req.username, &req.password, remote_ip.as_deref())
+        .await
+    {
         Ok(response) => Ok(HttpResponse::Ok().json(response)),
         Err(err) => Err(err),
     }
@@ -46,14 +167,193 @@ pub async fn login_handler(
 /// `service` - ユーザーログアウトサービスのインスタンス
 /// `req` - ログアウトリクエストのデータ
 /// 
-/// 成功・失敗に関わらず、HTTP 200 OK レスポンスを返す
+/// 成功した場合、HTTP 200 OK レスポンスを返す（セッションが既に存在しない場合も含む）
+/// 失敗した場合、AppError を返す
 pub async fn logout_handler(
     service: web::Data<AuthService<AuthRepositoryImpl>>,
     req: web::Json<LogoutRequestDto>,
 ) -> Result<HttpResponse, AppError> {
     match service.logout_user(&req.session_token).await {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
-        Err(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+/// セッショントークンを更新するハンドラー関数
+/// 
+/// `service` - 認証サービスのインスタンス
+/// `user` - 認証済みのユーザー
+/// 
+/// 成功した場合、HTTP 200 OK レスポンスと新しいセッショントークンを返す
+/// 失敗した場合、AppError を返す
+pub async fn refresh_session_handler<T: AuthRepository + std::fmt::Debug + 'static>(
+    service: web::Data<AuthService<T>>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    match service.refresh_session(&user.session.session_token).await {
+        Ok(response) => Ok(HttpResponse::Ok().json(response)),
+        Err(err) => Err(err),
+    }
+}
+
+/// 有効なセッションの一覧を取得するハンドラー関数
+/// 
+/// `service` - 認証サービスのインスタンス
+/// `user` - 認証済みのユーザー
+/// 
+/// 成功した場合、HTTP 200 OK レスポンスとセッションのリストを返す
+/// 失敗した場合、AppError を返す
+pub async fn list_sessions_handler<T: AuthRepository + std::fmt::Debug + 'static>(
+    service: web::Data<AuthService<T>>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    match service.list_sessions(&user).await {
+        Ok(sessions) => Ok(HttpResponse::Ok().json(sessions)),
+        Err(err) => Err(err),
+    }
+}
+
+/// 自分のセッションを1つ無効化するハンドラー関数
+/// 
+/// `service` - 認証サービスのインスタンス
+/// `user` - 認証済みのユーザー
+/// `path` - セッションIDのパスパラメータ
+/// 
+/// 成功した場合、HTTP 204 No Content レスポンスを返す
+/// セッションが見つからない場合、HTTP 404 Not Found を返す
+/// 失敗した場合、AppError を返す
+pub async fn revoke_session_handler<T: AuthRepository + std::fmt::Debug + 'static>(
+    service: web::Data<AuthService<T>>,
+    user: AuthenticatedUser,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .revoke_session(user.user.id, path.into_inner())
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+/// 自分の全てのセッションを無効化するハンドラー関数
+/// 
+/// `service` - 認証サービスのインスタンス
+/// `user` - 認証済みのユーザー
+/// 
+/// リクエストに使用したセッションも無効化される
+/// 
+/// 成功した場合、HTTP 200 OK レスポンスと無効化したセッションの数を返す
+/// 失敗した場合、AppError を返す
+pub async fn revoke_all_sessions_handler<T: AuthRepository + std::fmt::Debug + 'static>(
+    service: web::Data<AuthService<T>>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    match service.revoke_all_sessions(user.user.id).await {
+        Ok(revoked) => Ok(HttpResponse::Ok().json(RevokeSessionsResponseDto { revoked })),
+        Err(err) => Err(err),
+    }
+}
+
+/// 指定したユーザーの全てのセッションを無効化するハンドラー関数（管理者用）
+/// 
+/// `service` - 認証サービスのインスタンス
+/// `path` - ユーザーIDのパスパラメータ
+/// 
+/// 成功した場合、HTTP 200 OK レスポンスと無効化したセッションの数を返す
+/// 失敗した場合、AppError を返す
+pub async fn revoke_user_sessions_handler<T: AuthRepository + std::fmt::Debug + 'static>(
+    service: web::Data<AuthService<T>>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    match service.revoke_all_sessions(path.into_inner()).await {
+        Ok(revoked) => Ok(HttpResponse::Ok().json(RevokeSessionsResponseDto { revoked })),
+        Err(err) => Err(err),
+    }
+}
+
+/// セッションを管理するエンドポイントのスコープを作成する
+/// 
+/// `auth_service` - 認証ミドルウェアで使用する認証サービスのインスタンス
+/// 
+/// スコープ全体にログイン中のユーザーを要求し、`/user/{user_id}` は管理者のみに制限する
+pub fn session_scope<T: AuthRepository + std::fmt::Debug + 'static>(
+    auth_service: Arc<AuthService<T>>,
+) -> impl HttpServiceFactory {
+    web::scope("/session")
+        .wrap(AuthMiddleware::new(
+            auth_service.clone(),
+            &[Role::Client, Role::Dispatcher, Role::Driver, Role::Admin],
+        ))
+        .service(
+            web::resource("")
+                .route(web::get().to(list_sessions_handler::<T>))
+                .route(web::delete().to(revoke_all_sessions_handler::<T>)),
+        )
+        .service(web::resource("/refresh").route(web::post().to(refresh_session_handler::<T>)))
+        .service(
+            web::resource("/user/{user_id}")
+                .wrap(AuthMiddleware::new(auth_service, &[Role::Admin]))
+                .route(web::delete().to(revoke_user_sessions_handler::<T>)),
+        )
+        .service(web::resource("/{id}").route(web::delete().to(revoke_session_handler::<T>)))
+}
+
+/// パスワードを変更するハンドラー関数
+/// 
+/// `service` - 認証サービスのインスタンス
+/// `user` - 認証済みのユーザー
+/// `req` - パスワード変更リクエストのデータ
+/// 
+/// 成功した場合、HTTP 204 No Content レスポンスを返す
+/// 現在のパスワードが正しくない場合、HTTP 401 Unauthorized を返す
+/// 失敗した場合、AppError を返す
+pub async fn change_password_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    user: AuthenticatedUser,
+    req: web::Json<ChangePasswordRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .change_password(&user, &req.old_password, &req.new_password)
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+/// パスワードリセット用のトークンを発行するハンドラー関数（管理者用）
+/// 
+/// `service` - 認証サービスのインスタンス
+/// `path` - ユーザーIDのパスパラメータ
+/// 
+/// 成功した場合、HTTP 201 Created レスポンスとトークンを返す
+/// 失敗した場合、AppError を返す
+pub async fn issue_password_reset_token_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    match service.issue_password_reset_token(path.into_inner()).await {
+        Ok(response) => Ok(HttpResponse::Created().json(response)),
+        Err(err) => Err(err),
+    }
+}
+
+/// パスワードリセット用のトークンでパスワードを再設定するハンドラー関数
+/// 
+/// `service` - 認証サービスのインスタンス
+/// `req` - パスワード再設定リクエストのデータ
+/// 
+/// 成功した場合、HTTP 204 No Content レスポンスを返す
+/// トークンが無効な場合、HTTP 401 Unauthorized を返す
+/// 失敗した場合、AppError を返す
+pub async fn reset_password_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    req: web::Json<ResetPasswordRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    match service.reset_password(&req.token, &req.new_password).await {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(err) => Err(err),
     }
 }
 
@@ -76,4 +376,292 @@ pub async fn user_profile_image_handler(
     Ok(HttpResponse::Ok()
         .content_type("image/png")
         .body(profile_image_byte))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domains::auth_service::AuthConfig;
+    use crate::domains::clock::SystemClock;
+    use crate::domains::login_throttle::LoginThrottleConfig;
+    use crate::domains::password_policy::PasswordPolicy;
+    use crate::models::graph::Node;
+    use crate::models::user::{Session, SessionPolicy, User};
+    use crate::repositories::in_memory::InMemoryRepository;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::{test as actix_test, App};
+    use chrono::{Duration, Utc};
+
+    const DRIVER: i32 = 1;
+    const ADMIN: i32 = 2;
+    const OTHER_DRIVER: i32 = 3;
+
+    const DRIVER_TOKEN: &str = "driver-token";
+    const ADMIN_TOKEN: &str = "admin-token";
+
+    /// ドライバーのセッション（1: 使用中、2: 別の端末、3: 期限切れ）と、
+    /// 管理者（4）、別のドライバー（5）のセッションを用意する
+    fn session_repository() -> InMemoryRepository {
+        let now = Utc::now();
+        let repository = InMemoryRepository::default();
+        {
+            let mut data = repository.data();
+            for (id, role) in [
+                (DRIVER, Role::Driver),
+                (ADMIN, Role::Admin),
+                (OTHER_DRIVER, Role::Driver),
+            ] {
+                data.users.push(User {
+                    id,
+                    username: format!("user{}", id),
+                    password: String::new(),
+                    role: role.as_str().to_string(),
+                });
+            }
+            for (id, user_id, token, created_hours) in [
+                (1, DRIVER, DRIVER_TOKEN, 0),
+                (2, DRIVER, "driver-other-device", 1),
+                (3, DRIVER, "driver-expired", 25),
+                (4, ADMIN, ADMIN_TOKEN, 0),
+                (5, OTHER_DRIVER, "other-driver-token", 0),
+            ] {
+                data.sessions.push(Session {
+                    id,
+                    user_id,
+                    session_token: token.to_string(),
+                    is_valid: true,
+                    created_at: now - Duration::hours(created_hours),
+                    last_seen_at: now,
+                });
+            }
+        }
+        repository
+    }
+
+    fn auth_service(repository: InMemoryRepository) -> Arc<AuthService<InMemoryRepository>> {
+        let login_throttle = LoginThrottleConfig {
+            free_attempts: 3,
+            lockout_attempts: 10,
+            backoff_base: Duration::seconds(1),
+            lockout_duration: Duration::minutes(15),
+        };
+        Arc::new(AuthService::new(
+            repository,
+            AuthConfig {
+                session_policy: SessionPolicy {
+                    lifetime: Duration::hours(24),
+                    idle_timeout: Duration::hours(2),
+                },
+                username_throttle: login_throttle,
+                ip_throttle: login_throttle,
+                password_policy: PasswordPolicy {
+                    min_length: 8,
+                    max_length: 128,
+                    require_letter: false,
+                    require_digit: false,
+                    require_symbol: false,
+                },
+                password_reset_token_lifetime: Duration::hours(1),
+            },
+            Arc::new(SystemClock),
+        ))
+    }
+
+    /// セッションのスコープにリクエストを送り、ステータスコードとレスポンスの本文を返す
+    async fn request(
+        repository: &InMemoryRepository,
+        method: Method,
+        uri: &str,
+        token: &str,
+    ) -> (StatusCode, Option<serde_json::Value>) {
+        let service = auth_service(repository.clone());
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::from(service.clone()))
+                .service(web::scope("/api").service(session_scope(service))),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::default()
+            .method(method)
+            .uri(uri)
+            .insert_header(("Authorization", token))
+            .to_request();
+        match actix_test::try_call_service(&app, req).await {
+            Ok(res) => {
+                let status = res.status();
+                let body = actix_test::read_body(res).await;
+                (status, serde_json::from_slice(&body).ok())
+            }
+            Err(err) => (err.as_response_error().status_code(), None),
+        }
+    }
+
+    fn session_ids(repository: &InMemoryRepository) -> Vec<i32> {
+        repository
+            .data()
+            .sessions
+            .iter()
+            .map(|session| session.id)
+            .collect()
+    }
+
+    #[actix_rt::test]
+    async fn lists_own_unexpired_sessions() {
+        let repository = session_repository();
+
+        let (status, body) = request(&repository, Method::GET, "/api/session", DRIVER_TOKEN).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let sessions = body.unwrap();
+        let sessions = sessions.as_array().unwrap();
+        let ids: Vec<_> = sessions.iter().map(|session| &session["id"]).collect();
+        let current: Vec<_> = sessions.iter().map(|session| &session["current"]).collect();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(current, [true, false]);
+    }
+
+    #[actix_rt::test]
+    async fn revokes_only_own_session() {
+        let repository = session_repository();
+
+        let (own, _) = request(&repository, Method::DELETE, "/api/session/2", DRIVER_TOKEN).await;
+        let (other, _) = request(&repository, Method::DELETE, "/api/session/5", DRIVER_TOKEN).await;
+
+        assert_eq!(own, StatusCode::NO_CONTENT);
+        assert_eq!(other, StatusCode::NOT_FOUND);
+        assert_eq!(session_ids(&repository), [1, 3, 4, 5]);
+    }
+
+    #[actix_rt::test]
+    async fn revokes_all_own_sessions() {
+        let repository = session_repository();
+
+        let (status, body) =
+            request(&repository, Method::DELETE, "/api/session", DRIVER_TOKEN).await;
+        let (after, _) = request(&repository, Method::GET, "/api/session", DRIVER_TOKEN).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.unwrap()["revoked"], 3);
+        assert_eq!(after, StatusCode::UNAUTHORIZED);
+        assert_eq!(session_ids(&repository), [4, 5]);
+    }
+
+    #[actix_rt::test]
+    async fn only_admin_can_revoke_sessions_of_other_user() {
+        let repository = session_repository();
+        let uri = format!("/api/session/user/{}", OTHER_DRIVER);
+
+        let (driver, _) = request(&repository, Method::DELETE, &uri, DRIVER_TOKEN).await;
+        assert_eq!(driver, StatusCode::FORBIDDEN);
+        assert_eq!(session_ids(&repository), [1, 2, 3, 4, 5]);
+
+        let (admin, body) = request(&repository, Method::DELETE, &uri, ADMIN_TOKEN).await;
+        assert_eq!(admin, StatusCode::OK);
+        assert_eq!(body.unwrap()["revoked"], 1);
+        assert_eq!(session_ids(&repository), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn trusted_proxies_accept_addresses_and_cidr_ranges() {
+        let proxies = TrustedProxies::parse("10.0.0.1, 172.16.0.0/12, ::1, bogus, 10.0.0.0/33,");
+
+        for (ip, expected) in [
+            ("10.0.0.1", true),
+            ("10.0.0.2", false),
+            ("172.16.0.1", true),
+            ("172.31.255.255", true),
+            ("172.32.0.1", false),
+            ("::1", true),
+            ("::2", false),
+        ] {
+            assert_eq!(proxies.contains(ip.parse().unwrap()), expected, "{}", ip);
+        }
+        assert_eq!(proxies.networks.len(), 3);
+    }
+
+    /// エリア1に `role` のユーザーの登録リクエストを送り、ステータスコードを返す
+    async fn register_status(
+        repository: &InMemoryRepository,
+        role: Role,
+        token: Option<&str>,
+    ) -> StatusCode {
+        let service = auth_service(repository.clone());
+        let app = actix_test::init_service(
+            App::new().app_data(web::Data::from(service)).route(
+                "/api/register",
+                web::post().to(register_handler::<InMemoryRepository>),
+            ),
+        )
+        .await;
+
+        let mut req = actix_test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(serde_json::json!({
+                "username": format!("new-{}", role.as_str()),
+                "password": "password",
+                "role": role.as_str(),
+                "area_id": 1,
+            }));
+        if let Some(token) = token {
+            req = req.insert_header(("Authorization", token));
+        }
+        match actix_test::try_call_service(&app, req.to_request()).await {
+            Ok(res) => res.status(),
+            Err(err) => err.as_response_error().status_code(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn only_admin_can_register_dispatcher() {
+        let repository = session_repository();
+        repository.data().nodes.push((1, Node { id: 1, x: 0, y: 0 }));
+
+        let self_registration = register_status(&repository, Role::Dispatcher, None).await;
+        let by_driver = register_status(&repository, Role::Dispatcher, Some(DRIVER_TOKEN)).await;
+        let by_unknown_session =
+            register_status(&repository, Role::Dispatcher, Some("unknown-token")).await;
+        let by_admin = register_status(&repository, Role::Dispatcher, Some(ADMIN_TOKEN)).await;
+        let client = register_status(&repository, Role::Client, None).await;
+
+        assert_eq!(self_registration, StatusCode::FORBIDDEN);
+        assert_eq!(by_driver, StatusCode::FORBIDDEN);
+        assert_eq!(by_unknown_session, StatusCode::UNAUTHORIZED);
+        assert_eq!(by_admin, StatusCode::CREATED);
+        assert_eq!(client, StatusCode::CREATED);
+        assert_eq!(repository.data().dispatchers.len(), 1);
+    }
+
+    /// 接続元 `peer` から `X-Real-IP` ヘッダー付きで送られたリクエストのリクエスト元のIPアドレスを返す
+    fn client_ip(proxies: &TrustedProxies, peer: &str, real_ip: Option<&str>) -> String {
+        let mut req = actix_test::TestRequest::default().peer_addr(peer.parse().unwrap());
+        if let Some(real_ip) = real_ip {
+            req = req.insert_header(("X-Real-IP", real_ip));
+        }
+        proxies
+            .client_ip(&req.to_http_request())
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn uses_real_ip_header_only_from_trusted_proxy() {
+        let proxies = TrustedProxies::parse("172.16.0.0/12");
+        let proxy = "172.18.0.2:1234";
+
+        assert_eq!(
+            client_ip(&proxies, proxy, Some("203.0.113.7")),
+            "203.0.113.7"
+        );
+        assert_eq!(client_ip(&proxies, proxy, None), "172.18.0.2");
+        assert_eq!(client_ip(&proxies, proxy, Some("not-an-ip")), "172.18.0.2");
+        assert_eq!(
+            client_ip(&proxies, "198.51.100.1:1234", Some("203.0.113.7")),
+            "198.51.100.1"
+        );
+        assert_eq!(
+            client_ip(&TrustedProxies::default(), proxy, Some("203.0.113.7")),
+            "172.18.0.2"
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     domains::{dto::map::UpdateEdgeRequestDto, map_service::MapService},
     errors::AppError,
+    models::user::AuthenticatedUser,
     repositories::map_repository::MapRepositoryImpl,
 };
 use actix_web::{web, HttpResponse};
@@ -8,16 +9,21 @@ use actix_web::{web, HttpResponse};
 /// エッジ更新リクエストを処理するハンドラー関数
 /// 
 /// `service` - エッジ更新サービスのインスタンス
+/// `user` - 認証済みのユーザー
 /// `req` - エッジ更新リクエストのデータ
 /// 
+/// 重みが負、または大きすぎる場合は HTTP 400 Bad Request を返す
+/// エッジの両端のノードがディスパッチャーの担当エリア外の場合は HTTP 403 Forbidden を返す
+/// 
 /// 成功した場合、HTTP 200 OK レスポンスを返す
 /// 失敗した場合、AppError を返す
 pub async fn update_edge_handler(
     service: web::Data<MapService<MapRepositoryImpl>>,
+    user: AuthenticatedUser,
     req: web::Json<UpdateEdgeRequestDto>,
 ) -> Result<HttpResponse, AppError> {
     match service
-        .update_edge(req.node_a_id, req.node_b_id, req.weight)
+        .update_edge(&user, req.node_a_id, req.node_b_id, req.weight)
         .await
     {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
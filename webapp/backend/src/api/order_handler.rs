@@ -1,8 +1,10 @@
 use crate::domains::dto::order::{
-    ClientOrderRequestDto, DispatcherOrderRequestDto, UpdateOrderStatusRequestDto,
+    CancelOrderRequestDto, ClientOrderRequestDto, CompleteOrderRequestDto, DispatcherOrderRequestDto,
+    UpdateOrderStatusRequestDto,
 };
 use crate::domains::order_service::OrderService;
 use crate::errors::AppError;
+use crate::models::user::AuthenticatedUser;
 use crate::repositories::auth_repository::AuthRepositoryImpl;
 use crate::repositories::map_repository::MapRepositoryImpl;
 use crate::repositories::order_repository::OrderRepositoryImpl;
@@ -13,8 +15,11 @@ use serde::Deserialize;
 /// 注文ステータス更新リクエストを処理するハンドラー関数
 /// 
 /// `service` - 注文サービスのインスタンス
+/// `user` - 認証済みのユーザー
 /// `req` - 注文ステータス更新リクエストのデータ
 /// 
+/// 注文に割り当てられたドライバーでも、注文と同じエリアのディスパッチャーでもない場合は HTTP 403 Forbidden を返す
+/// 
 /// 成功した場合、HTTP 200 OK レスポンスを返す
 /// 失敗した場合、AppError を返す
 pub async fn update_order_status_handler(
@@ -26,9 +31,70 @@ pub async fn update_order_status_handler(
             MapRepositoryImpl,
         >,
     >,
+    user: AuthenticatedUser,
     req: web::Json<UpdateOrderStatusRequestDto>,
 ) -> Result<HttpResponse, AppError> {
-    match service.update_order_status(req.order_id, &req.status).await {
+    match service
+        .update_order_status(req.order_id, &req.status, &user)
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+/// 注文完了リクエストを処理するハンドラー関数
+/// 
+/// `service` - 注文サービスのインスタンス
+/// `user` - 認証済みのユーザー
+/// `req` - 注文完了リクエストのデータ
+/// 
+/// 注文に割り当てられたドライバーでも、注文と同じエリアのディスパッチャーでもない場合は HTTP 403 Forbidden を返す
+/// 
+/// 成功した場合、HTTP 200 OK レスポンスを返す
+/// 失敗した場合、AppError を返す
+pub async fn complete_order_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    user: AuthenticatedUser,
+    req: web::Json<CompleteOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    match service.complete_order(req.order_id, &user).await {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+/// 注文キャンセルリクエストを処理するハンドラー関数
+/// 
+/// `service` - 注文サービスのインスタンス
+/// `user` - 認証済みのユーザー
+/// `req` - 注文キャンセルリクエストのデータ
+/// 
+/// 成功した場合、HTTP 200 OK レスポンスを返す
+/// 失敗した場合、AppError を返す
+pub async fn cancel_order_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    user: AuthenticatedUser,
+    req: web::Json<CancelOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .cancel_order(req.order_id, &req.reason, &user)
+        .await
+    {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
         Err(err) => Err(err),
     }
@@ -37,6 +103,7 @@ pub async fn update_order_status_handler(
 /// 注文IDに基づいて注文情報を取得するハンドラー関数
 /// 
 /// `service` - 注文サービスのインスタンス
+/// `user` - 認証済みのユーザー
 /// `path` - 注文IDのパスパラメータ
 /// 
 /// 成功した場合、HTTP 200 OK レスポンスと注文情報を返す
@@ -50,9 +117,10 @@ pub async fn get_order_handler(
             MapRepositoryImpl,
         >,
     >,
+    user: AuthenticatedUser,
     path: web::Path<i32>,
 ) -> Result<HttpResponse, AppError> {
-    match service.get_order_by_id(path.into_inner()).await {
+    match service.get_order_by_id(path.into_inner(), &user).await {
         Ok(order) => Ok(HttpResponse::Ok().json(order)),
         Err(err) => Err(err),
     }
@@ -72,8 +140,11 @@ pub struct PaginatedOrderQuery {
 /// ページネーションされた注文リストを取得するハンドラー関数
 /// 
 /// `service` - 注文サービスのインスタンス
+/// `user` - 認証済みのユーザー
 /// `query` - ページネーションとフィルタリングのクエリパラメータ
 /// 
+/// エリアはディスパッチャーの担当エリアに限定され、それ以外のエリアを指定した場合は HTTP 403 Forbidden を返す
+/// 
 /// 成功した場合、HTTP 200 OK レスポンスと注文リストを返す
 /// 失敗した場合、AppError を返す
 /// 
@@ -88,8 +159,11 @@ pub async fn get_paginated_orders_handler(
             MapRepositoryImpl,
         >,
     >,
+    user: AuthenticatedUser,
     query: web::Query<PaginatedOrderQuery>,
 ) -> Result<HttpResponse, AppError> {
+    let area_id = user.scoped_area_id(query.area)?;
+
     match service
         .get_paginated_orders(
             query.page.unwrap_or(0),
@@ -97,7 +171,7 @@ pub async fn get_paginated_orders_handler(
             query.sort_by.clone(),
             query.sort_order.clone(),
             query.status.clone(),
-            query.area,
+            Some(area_id),
         )
         .await
     {
@@ -109,8 +183,11 @@ pub async fn get_paginated_orders_handler(
 /// クライアント注文を作成するハンドラー関数
 /// 
 /// `service` - 注文サービスのインスタンス
+/// `user` - 認証済みのユーザー
 /// `req` - クライアント注文リクエストのデータ
 /// 
+/// クライアントIDはセッションから取得し、リクエストで異なるIDが指定された場合は HTTP 403 Forbidden を返す
+/// 
 /// 成功した場合、HTTP 201 Created レスポンスを返す
 /// 失敗した場合、AppError を返す
 pub async fn create_client_order_handler(
@@ -122,10 +199,13 @@ pub async fn create_client_order_handler(
             MapRepositoryImpl,
         >,
     >,
+    user: AuthenticatedUser,
     req: web::Json<ClientOrderRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    let client_id = user.client_id(req.client_id)?;
+
     match service
-        .create_client_order(req.client_id, req.node_id, req.car_value)
+        .create_client_order(client_id, req.node_id, req.car_value)
         .await
     {
         Ok(_) => Ok(HttpResponse::Created().finish()),
@@ -136,8 +216,12 @@ pub async fn create_client_order_handler(
 /// ディスパッチャー注文を作成するハンドラー関数
 /// 
 /// `service` - 注文サービスのインスタンス
+/// `user` - 認証済みのユーザー
 /// `req` - ディスパッチャー注文リクエストのデータ
 /// 
+/// ディスパッチャーIDはセッションから取得し、リクエストで異なるIDが指定された場合や
+/// 注文またはレッカー車がディスパッチャーの担当エリア外の場合は HTTP 403 Forbidden を返す
+/// 
 /// 成功した場合、HTTP 200 OK レスポンスを返す
 /// 失敗した場合、AppError を返す
 pub async fn create_dispatcher_order_handler(
@@ -149,12 +233,17 @@ pub async fn create_dispatcher_order_handler(
             MapRepositoryImpl,
         >,
     >,
+    user: AuthenticatedUser,
     req: web::Json<DispatcherOrderRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    let dispatcher_id = user.dispatcher_id(req.dispatcher_id)?;
+    let area_id = user.scoped_area_id(None)?;
+
     match service
         .create_dispatcher_order(
             req.order_id,
-            req.dispatcher_id,
+            dispatcher_id,
+            area_id,
             req.tow_truck_id,
             req.order_time,
         )
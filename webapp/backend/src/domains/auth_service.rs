@@ -1,156 +1,366 @@
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
 
 use actix_web::web::Bytes;
+use chrono::{DateTime, Duration, Utc};
 use log::error;
 
-use crate::errors::AppError;
-use crate::models::user::{Dispatcher, Session, User};
-use crate::utils::{generate_session_token, hash_password, verify_password};
+use crate::errors::{AppError, FieldError};
+use crate::models::user::{AuthenticatedUser, Dispatcher, Role, Session, SessionPolicy, User};
+use crate::utils::{generate_session_token, hash_password, hash_token};
 
-use super::dto::auth::LoginResponseDto;
+use super::clock::Clock;
+use super::dto::auth::{
+    LoginResponseDto, PasswordResetTokenDto, RefreshSessionResponseDto, SessionDto,
+};
+use super::login_throttle::{LoginThrottle, LoginThrottleConfig};
+use super::password_policy::PasswordPolicy;
+
+/// セッションの最終利用時刻を更新する間隔
+///
+/// リクエストごとの書き込みを避けるため、前回の更新からこの時間が経過した場合のみ更新する
+const SESSION_TOUCH_INTERVAL_SECONDS: i64 = 60;
+
+/// ユーザー名の最大文字数（`users.username` の列の長さ）
+const USERNAME_MAX_LENGTH: usize = 255;
 
 /// 認証リポジトリのトレイト
 pub trait AuthRepository {
-    async fn create_user(&self, username: &str, password: &str, role: &str) -> Result<(), AppError>;
+    async fn create_user(&self, username: &str, password: &str, role: Role, area_id: Option<i32>, session_token: &str, now: DateTime<Utc>) -> Result<(User, Option<Dispatcher>), AppError>;
     async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError>;
     async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError>;
-    async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError>;
+    async fn authenticate_user(&self, username: &str, password: &str) -> Result<User, AppError>;
     async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError>;
     async fn find_dispatcher_by_user_id(&self, user_id: i32) -> Result<Option<Dispatcher>, AppError>;
     async fn find_profile_image_name_by_user_id(&self, user_id: i32) -> Result<Option<String>, AppError>;
-    async fn authenticate_user(&self, username: &str, password: &str) -> Result<User, AppError>;
-    async fn create_session(&self, user_id: i32, session_token: &str) -> Result<(), AppError>;
+    async fn create_session(&self, user_id: i32, session_token: &str, now: DateTime<Utc>) -> Result<(), AppError>;
     async fn delete_session(&self, session_token: &str) -> Result<(), AppError>;
     async fn find_session_by_session_token(&self, session_token: &str) -> Result<Session, AppError>;
+    async fn touch_session(&self, session_id: i32, last_seen_at: DateTime<Utc>) -> Result<(), AppError>;
+    async fn rotate_session(&self, session_token: &str, new_session_token: &str, now: DateTime<Utc>) -> Result<(), AppError>;
+    async fn delete_expired_sessions(&self, created_before: DateTime<Utc>, last_seen_before: DateTime<Utc>) -> Result<u64, AppError>;
+    async fn find_sessions_by_user_id(&self, user_id: i32) -> Result<Vec<Session>, AppError>;
+    async fn delete_session_by_id(&self, user_id: i32, session_id: i32) -> Result<u64, AppError>;
+    async fn delete_sessions_by_user_id(&self, user_id: i32) -> Result<u64, AppError>;
+    async fn update_password(&self, user_id: i32, password: &str, keep_session_id: i32) -> Result<(), AppError>;
+    async fn create_password_reset_token(&self, user_id: i32, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), AppError>;
+    async fn reset_password_with_token(&self, token_hash: &str, password: &str, now: DateTime<Utc>) -> Result<bool, AppError>;
+}
+
+/// 認証サービスの設定を表す構造体
+///
+/// `session_policy` - セッションの有効期限
+/// `username_throttle` - ユーザー名ごとのログイン試行の制限
+/// `ip_throttle` - IPアドレスごとのログイン試行の制限
+/// `password_policy` - パスワードの強度
+/// `password_reset_token_lifetime` - パスワードリセット用のトークンの有効期間
+#[derive(Clone, Copy, Debug)]
+pub struct AuthConfig {
+    pub session_policy: SessionPolicy,
+    pub username_throttle: LoginThrottleConfig,
+    pub ip_throttle: LoginThrottleConfig,
+    pub password_policy: PasswordPolicy,
+    pub password_reset_token_lifetime: Duration,
 }
 
 /// 認証サービスの構造体
 #[derive(Debug)]
 pub struct AuthService<T: AuthRepository + std::fmt::Debug> {
     repository: T,
+    session_policy: SessionPolicy,
+    username_throttle: LoginThrottle,
+    ip_throttle: LoginThrottle,
+    password_policy: PasswordPolicy,
+    password_reset_token_lifetime: Duration,
+    clock: Arc<dyn Clock>,
 }
 
 impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
     /// 新しい認証サービスを作成する
-    pub fn new(repository: T) -> Self {
-        AuthService { repository }
+    ///
+    /// `clock` - セッションの有効期限やログイン試行の制限の判定に用いる時計
+    pub fn new(repository: T, config: AuthConfig, clock: Arc<dyn Clock>) -> Self {
+        AuthService {
+            repository,
+            session_policy: config.session_policy,
+            username_throttle: LoginThrottle::new(config.username_throttle),
+            ip_throttle: LoginThrottle::new(config.ip_throttle),
+            password_policy: config.password_policy,
+            password_reset_token_lifetime: config.password_reset_token_lifetime,
+            clock,
+        }
     }
 
     /// ユーザーを登録する
+    ///
+    /// `role` - ユーザーのロール（`admin` は登録できない）
+    /// `area` - ディスパッチャーの担当エリアID（ディスパッチャーの場合は必須）
+    /// `caller` - 登録を行う認証済みのユーザー（自身で登録する場合は `None`）
+    ///
+    /// 自身で登録できるのはクライアントとドライバーのみで、ディスパッチャーは管理者のみ登録できる。
+    /// それ以外の場合は `AppError::Forbidden` を返す。
+    /// ユーザー、セッション、ディスパッチャーは1つのトランザクションで作成する。
+    /// ユーザー名が既に使われている場合は 409 を、
+    /// エリアが存在しない場合は `AppError::InvalidArea` を返す
     pub async fn register_user(
         &self,
         username: &str,
         password: &str,
         role: &str,
         area: Option<i32>,
+        caller: Option<&AuthenticatedUser>,
     ) -> Result<LoginResponseDto, AppError> {
-        if role == "dispatcher" && area.is_none() {
-            return Err(AppError::BadRequest);
-        }
-
-        if (self.repository.find_user_by_username(username).await?).is_some() {
-            return Err(AppError::Conflict);
-        }
-
-        let hashed_password = hash_password(password).unwrap();
-
-        self.repository
-            .create_user(username, &hashed_password, role)
-            .await?;
+        let role: Role = role.parse()?;
+        let is_admin = caller.is_some_and(|caller| caller.user.has_role(Role::Admin));
+        let area = match (role, area) {
+            (Role::Admin, _) => return Err(AppError::Forbidden),
+            (Role::Dispatcher, _) if !is_admin => return Err(AppError::Forbidden),
+            (Role::Dispatcher, None) => {
+                return Err(AppError::ValidationFailed(vec![FieldError::new(
+                    "area_id",
+                    "required for dispatcher",
+                )]))
+            }
+            (Role::Dispatcher, area) => area,
+            _ => None,
+        };
+        validate_username(username)?;
+        self.password_policy.validate("password", password)?;
 
+        let hashed_password = hash_password(password)?;
         let session_token = generate_session_token();
+        let (user, dispatcher) = self
+            .repository
+            .create_user(
+                username,
+                &hashed_password,
+                role,
+                area,
+                &session_token,
+                self.clock.now(),
+            )
+            .await?;
 
-        match self.repository.find_user_by_username(username).await? {
-            Some(user) => {
-                self.repository
-                    .create_session(user.id, &session_token)
-                    .await?;
-                match user.role.as_str() {
-                    "dispatcher" => {
-                        self.repository
-                            .create_dispatcher(user.id, area.unwrap())
-                            .await?;
-                        let dispatcher = self
-                            .repository
-                            .find_dispatcher_by_user_id(user.id)
-                            .await?
-                            .unwrap();
-                        Ok(LoginResponseDto {
-                            user_id: user.id,
-                            username: user.username,
-                            session_token,
-                            role: user.role,
-                            dispatcher_id: Some(dispatcher.id),
-                            area_id: Some(dispatcher.area_id),
-                        })
-                    }
-                    _ => Ok(LoginResponseDto {
-                        user_id: user.id,
-                        username: user.username,
-                        session_token,
-                        role: user.role,
-                        dispatcher_id: None,
-                        area_id: None,
-                    }),
-                }
-            }
-            None => Err(AppError::InternalServerError),
-        }
+        Ok(LoginResponseDto {
+            user_id: user.id,
+            username: user.username,
+            session_token,
+            role: user.role,
+            dispatcher_id: dispatcher.as_ref().map(|dispatcher| dispatcher.id),
+            area_id: dispatcher.as_ref().map(|dispatcher| dispatcher.area_id),
+        })
     }
 
     /// ユーザーをログインさせる
+    ///
+    /// `remote_ip` - リクエスト元のIPアドレス
+    ///
+    /// ユーザー名またはIPアドレスごとの連続失敗が続いている場合、パスワードを検証せずに
+    /// `AppError::TooManyLoginAttempts` を返す。
+    /// 並行した試行がまとめて制限をすり抜けないよう、試行はパスワードの検証前に失敗として記録し、
+    /// 認証に失敗しなかった場合に取り消す
     pub async fn login_user(
         &self,
         username: &str,
         password: &str,
+        remote_ip: Option<&str>,
     ) -> Result<LoginResponseDto, AppError> {
-        match self.repository.find_user_by_username(username).await? {
-            Some(user) => {
-                let is_password_valid = verify_password(&user.password, password).unwrap();
-                if !is_password_valid {
-                    return Err(AppError::Unauthorized);
-                }
+        let now = self.clock.now();
+        self.username_throttle.begin_attempt(username, now)?;
+        if let Some(remote_ip) = remote_ip {
+            if let Err(err) = self.ip_throttle.begin_attempt(remote_ip, now) {
+                self.username_throttle.forgive_attempt(username);
+                return Err(err);
+            }
+        }
+        let forgive_ip_attempt = || {
+            if let Some(remote_ip) = remote_ip {
+                self.ip_throttle.forgive_attempt(remote_ip);
+            }
+        };
 
-                let session_token = generate_session_token();
-                self.repository
-                    .create_session(user.id, &session_token)
-                    .await?;
-
-                match user.role.as_str() {
-                    "dispatcher" => {
-                        match self.repository.find_dispatcher_by_user_id(user.id).await? {
-                            Some(dispatcher) => Ok(LoginResponseDto {
-                                user_id: user.id,
-                                username: user.username,
-                                session_token,
-                                role: user.role.clone(),
-                                dispatcher_id: Some(dispatcher.id),
-                                area_id: Some(dispatcher.area_id),
-                            }),
-                            None => Err(AppError::InternalServerError),
-                        }
-                    }
-                    _ => Ok(LoginResponseDto {
+        let user = match self.repository.authenticate_user(username, password).await {
+            Ok(user) => user,
+            Err(AppError::Unauthorized) => return Err(AppError::Unauthorized),
+            Err(err) => {
+                self.username_throttle.forgive_attempt(username);
+                forgive_ip_attempt();
+                return Err(err);
+            }
+        };
+        // 他のアカウントへの試行を隠せないよう、IPアドレスの記録は成功した試行の分だけ取り消す
+        self.username_throttle.record_success(username);
+        forgive_ip_attempt();
+
+        let session_token = generate_session_token();
+        self.repository
+            .create_session(user.id, &session_token, self.clock.now())
+            .await?;
+
+        match user.role.parse::<Role>() {
+            Ok(Role::Dispatcher) => {
+                match self.repository.find_dispatcher_by_user_id(user.id).await? {
+                    Some(dispatcher) => Ok(LoginResponseDto {
                         user_id: user.id,
                         username: user.username,
                         session_token,
                         role: user.role.clone(),
-                        dispatcher_id: None,
-                        area_id: None,
+                        dispatcher_id: Some(dispatcher.id),
+                        area_id: Some(dispatcher.area_id),
                     }),
+                    None => Err(AppError::InternalServerError),
                 }
             }
-            None => Err(AppError::Unauthorized),
+            _ => Ok(LoginResponseDto {
+                user_id: user.id,
+                username: user.username,
+                session_token,
+                role: user.role.clone(),
+                dispatcher_id: None,
+                area_id: None,
+            }),
         }
     }
 
     /// ユーザーをログアウトさせる
+    ///
+    /// セッションが既に存在しない場合も成功として扱う
     pub async fn logout_user(&self, session_token: &str) -> Result<(), AppError> {
         self.repository.delete_session(session_token).await?;
         Ok(())
     }
 
+    /// ユーザーの有効なセッションの一覧を取得する
+    ///
+    /// `user` - 認証済みのユーザー
+    ///
+    /// 期限切れのセッションは含まず、リクエストに使用したセッションには `current` が付く
+    pub async fn list_sessions(&self, user: &AuthenticatedUser) -> Result<Vec<SessionDto>, AppError> {
+        let now = self.clock.now();
+        let sessions = self.repository.find_sessions_by_user_id(user.user.id).await?;
+        Ok(sessions
+            .into_iter()
+            .filter(|session| session.is_valid && !self.session_policy.is_expired(session, now))
+            .map(|session| SessionDto::from_entity(session, user.session.id))
+            .collect())
+    }
+
+    /// ユーザーのセッションを1つ無効化する
+    ///
+    /// `user_id` - ユーザーID
+    /// `session_id` - セッションID
+    ///
+    /// ユーザーのセッションが存在しない場合は `AppError::NotFound` を返す
+    pub async fn revoke_session(&self, user_id: i32, session_id: i32) -> Result<(), AppError> {
+        match self
+            .repository
+            .delete_session_by_id(user_id, session_id)
+            .await?
+        {
+            0 => Err(AppError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// ユーザーの全てのセッションを無効化する
+    ///
+    /// `user_id` - ユーザーID
+    ///
+    /// 無効化したセッションの数を返す
+    pub async fn revoke_all_sessions(&self, user_id: i32) -> Result<u64, AppError> {
+        self.repository.delete_sessions_by_user_id(user_id).await
+    }
+
+    /// パスワードを変更する
+    ///
+    /// `user` - 認証済みのユーザー
+    /// `old_password` - 現在のパスワード
+    /// `new_password` - 新しいパスワード
+    ///
+    /// 現在のパスワードが正しくない場合は `AppError::Unauthorized` を返す。
+    /// 現在のパスワードの検証はログインと同じユーザー名ごとの試行の制限を受ける。
+    /// 変更後はリクエストに使用したセッション以外のセッションを無効化する
+    pub async fn change_password(
+        &self,
+        user: &AuthenticatedUser,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<(), AppError> {
+        let username = user.user.username.as_str();
+        self.username_throttle
+            .begin_attempt(username, self.clock.now())?;
+
+        // 認証後にパスワードが変更されている可能性があるため、最新のハッシュで検証する
+        match self
+            .repository
+            .authenticate_user(username, old_password)
+            .await
+        {
+            Ok(_) => {}
+            Err(AppError::Unauthorized) => return Err(AppError::Unauthorized),
+            Err(err) => {
+                self.username_throttle.forgive_attempt(username);
+                return Err(err);
+            }
+        }
+        self.username_throttle.record_success(username);
+
+        self.password_policy.validate("new_password", new_password)?;
+        let hashed_password = hash_password(new_password)?;
+        self.repository
+            .update_password(user.user.id, &hashed_password, user.session.id)
+            .await
+    }
+
+    /// 管理者がユーザーのパスワードリセット用のトークンを発行する
+    ///
+    /// `user_id` - ユーザーID
+    ///
+    /// 発行したトークンは一度だけ使用でき、以前に発行した未使用のトークンは無効になる。
+    /// データベースにはトークンのハッシュ値のみを保存する。
+    /// ユーザーの全てのセッションは、トークンの保存と同じトランザクションで無効化される
+    pub async fn issue_password_reset_token(
+        &self,
+        user_id: i32,
+    ) -> Result<PasswordResetTokenDto, AppError> {
+        if self.repository.find_user_by_id(user_id).await?.is_none() {
+            return Err(AppError::NotFound);
+        }
+
+        let token = generate_session_token();
+        let expires_at = self.clock.now() + self.password_reset_token_lifetime;
+        self.repository
+            .create_password_reset_token(user_id, &hash_token(&token), expires_at)
+            .await?;
+
+        Ok(PasswordResetTokenDto {
+            user_id,
+            token,
+            expires_at,
+        })
+    }
+
+    /// パスワードリセット用のトークンを使用してパスワードを再設定する
+    ///
+    /// `token` - パスワードリセット用のトークン
+    /// `new_password` - 新しいパスワード
+    ///
+    /// トークンが存在しない、使用済み、または期限切れの場合は `AppError::Unauthorized` を返す
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> Result<(), AppError> {
+        self.password_policy.validate("new_password", new_password)?;
+        let hashed_password = hash_password(new_password)?;
+
+        match self
+            .repository
+            .reset_password_with_token(&hash_token(token), &hashed_password, self.clock.now())
+            .await?
+        {
+            true => Ok(()),
+            false => Err(AppError::Unauthorized),
+        }
+    }
+
     /// プロフィール画像をリサイズして取得する
     pub async fn get_resized_profile_image_byte(&self, user_id: i32) -> Result<Bytes, AppError> {
         let profile_image_name = match self
@@ -189,13 +399,338 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
         }
     }
 
-    /// セッションを検証する
-    pub async fn validate_session(&self, session_token: &str) -> Result<bool, AppError> {
-        let session = self
+    /// セッションを検証し、認証済みのユーザーを取得する
+    ///
+    /// `session_token` - セッショントークン
+    ///
+    /// セッションが無効または期限切れの場合や、ユーザーが存在しない場合は `AppError::Unauthorized` を返す
+    pub async fn authenticate_session(
+        &self,
+        session_token: &str,
+    ) -> Result<AuthenticatedUser, AppError> {
+        let mut session = self
             .repository
             .find_session_by_session_token(session_token)
             .await?;
+        let now = self.clock.now();
+        if !session.is_valid || self.session_policy.is_expired(&session, now) {
+            return Err(AppError::Unauthorized);
+        }
+        if now - session.last_seen_at >= Duration::seconds(SESSION_TOUCH_INTERVAL_SECONDS) {
+            self.repository.touch_session(session.id, now).await?;
+            session.last_seen_at = now;
+        }
+
+        let user = self
+            .repository
+            .find_user_by_id(session.user_id)
+            .await?
+            .ok_or(AppError::Unauthorized)?;
+        let dispatcher = match user.role.parse::<Role>() {
+            Ok(Role::Dispatcher) => self.repository.find_dispatcher_by_user_id(user.id).await?,
+            _ => None,
+        };
+
+        Ok(AuthenticatedUser {
+            user,
+            session,
+            dispatcher,
+        })
+    }
+
+    /// セッショントークンを新しいトークンに置き換える
+    ///
+    /// `session_token` - 現在のセッショントークン
+    ///
+    /// 置き換え後は古いトークンは使用できない。
+    /// セッションの有効期間は延長されず、新しいトークンもセッションの作成時刻から `lifetime` が経過すると期限切れになる
+    pub async fn refresh_session(
+        &self,
+        session_token: &str,
+    ) -> Result<RefreshSessionResponseDto, AppError> {
+        let new_session_token = generate_session_token();
+        self.repository
+            .rotate_session(session_token, &new_session_token, self.clock.now())
+            .await?;
+
+        Ok(RefreshSessionResponseDto {
+            session_token: new_session_token,
+        })
+    }
+
+    /// 期限切れのセッションを削除する
+    ///
+    /// 削除したセッションの数を返す
+    pub async fn delete_expired_sessions(&self) -> Result<u64, AppError> {
+        let now = self.clock.now();
+        self.repository
+            .delete_expired_sessions(
+                now - self.session_policy.lifetime,
+                now - self.session_policy.idle_timeout,
+            )
+            .await
+    }
+}
+
+/// ユーザー名が空でなく、前後に空白や制御文字を含まず、`USERNAME_MAX_LENGTH` 文字以内であることを検証する
+///
+/// 満たしていない場合は `AppError::ValidationFailed` を返す
+fn validate_username(username: &str) -> Result<(), AppError> {
+    let invalid = |message: String| {
+        Err(AppError::ValidationFailed(vec![FieldError::new(
+            "username", message,
+        )]))
+    };
+
+    if username.trim().is_empty() {
+        return invalid("must not be empty".to_string());
+    }
+    if username.chars().count() > USERNAME_MAX_LENGTH {
+        return invalid(format!(
+            "must be at most {} characters",
+            USERNAME_MAX_LENGTH
+        ));
+    }
+    if username.trim() != username || username.chars().any(char::is_control) {
+        return invalid(
+            "must not contain surrounding whitespace or control characters".to_string(),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
 
-        Ok(session.is_valid)
+    use super::*;
+    use crate::domains::clock::FakeClock;
+    use crate::repositories::in_memory::InMemoryRepository;
+
+    const USERNAME: &str = "driver1";
+    const PASSWORD: &str = "password";
+    const SESSION_TOKEN: &str = "session-token";
+    const REMOTE_IP: &str = "192.0.2.1";
+
+    fn session_policy() -> SessionPolicy {
+        SessionPolicy {
+            lifetime: Duration::hours(24),
+            idle_timeout: Duration::hours(2),
+        }
     }
-}
\ No newline at end of file
+
+    fn auth_service(
+        repository: InMemoryRepository,
+        clock: Arc<FakeClock>,
+    ) -> AuthService<InMemoryRepository> {
+        let login_throttle = LoginThrottleConfig {
+            free_attempts: 3,
+            lockout_attempts: 10,
+            backoff_base: Duration::seconds(1),
+            lockout_duration: Duration::minutes(15),
+        };
+        AuthService::new(
+            repository,
+            AuthConfig {
+                session_policy: session_policy(),
+                username_throttle: login_throttle,
+                ip_throttle: login_throttle,
+                password_policy: PasswordPolicy {
+                    min_length: 8,
+                    max_length: 128,
+                    require_letter: false,
+                    require_digit: false,
+                    require_symbol: false,
+                },
+                password_reset_token_lifetime: Duration::hours(1),
+            },
+            clock,
+        )
+    }
+
+    fn clock() -> Arc<FakeClock> {
+        Arc::new(FakeClock::new(
+            Utc.with_ymd_and_hms(2024, 7, 25, 0, 0, 0).unwrap(),
+        ))
+    }
+
+    /// パスワードが `PASSWORD` のドライバーと、`created_at` に作成されたセッションを用意する
+    fn repository_with_session(created_at: DateTime<Utc>) -> InMemoryRepository {
+        let repository = InMemoryRepository::default();
+        {
+            let mut data = repository.data();
+            data.users.push(User {
+                id: 1,
+                username: USERNAME.to_string(),
+                password: hash_password(PASSWORD).unwrap(),
+                role: Role::Driver.as_str().to_string(),
+            });
+            data.sessions.push(Session {
+                id: 1,
+                user_id: 1,
+                session_token: SESSION_TOKEN.to_string(),
+                is_valid: true,
+                created_at,
+                last_seen_at: created_at,
+            });
+        }
+        repository
+    }
+
+    fn retry_after<U>(result: Result<U, AppError>) -> Option<i64> {
+        match result {
+            Err(AppError::TooManyLoginAttempts {
+                retry_after_seconds,
+            }) => Some(retry_after_seconds),
+            _ => None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn refreshed_session_expires_at_original_lifetime() {
+        let clock = clock();
+        let created_at = clock.now();
+        let service = auth_service(repository_with_session(created_at), clock.clone());
+
+        clock.advance(Duration::hours(23));
+        let refreshed = service.refresh_session(SESSION_TOKEN).await.unwrap();
+        let user = service
+            .authenticate_session(&refreshed.session_token)
+            .await
+            .unwrap();
+        assert!(service.authenticate_session(SESSION_TOKEN).await.is_err());
+        assert_eq!(user.session.created_at, created_at);
+
+        clock.advance(Duration::hours(1));
+        assert!(matches!(
+            service.authenticate_session(&refreshed.session_token).await,
+            Err(AppError::Unauthorized)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn login_creates_session_at_clock_time() {
+        let clock = clock();
+        let repository = repository_with_session(clock.now());
+        let service = auth_service(repository.clone(), clock.clone());
+
+        clock.advance(Duration::hours(30));
+        let login = service.login_user(USERNAME, PASSWORD, None).await.unwrap();
+
+        let data = repository.data();
+        let session = data
+            .sessions
+            .iter()
+            .find(|session| session.session_token == login.session_token)
+            .unwrap();
+        assert_eq!(session.created_at, clock.now());
+        assert_eq!(session.last_seen_at, clock.now());
+    }
+
+    #[actix_rt::test]
+    async fn register_rejects_invalid_username() {
+        let clock = clock();
+        let service = auth_service(InMemoryRepository::default(), clock);
+
+        for username in ["", "   ", " padded", "line\nbreak", &"a".repeat(256)] {
+            let result = service
+                .register_user(username, PASSWORD, "client", None, None)
+                .await;
+            assert!(
+                matches!(result, Err(AppError::ValidationFailed(_))),
+                "{:?}",
+                username
+            );
+        }
+        assert!(service
+            .register_user(&"a".repeat(255), PASSWORD, "client", None, None)
+            .await
+            .is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn login_backs_off_after_repeated_failures() {
+        let clock = clock();
+        let service = auth_service(repository_with_session(clock.now()), clock.clone());
+
+        for _ in 0..4 {
+            let result = service.login_user(USERNAME, "wrong", None).await;
+            assert!(matches!(result, Err(AppError::Unauthorized)));
+        }
+        // 待ち時間中は正しいパスワードでもログインできない
+        let result = service.login_user(USERNAME, PASSWORD, None).await;
+        assert_eq!(retry_after(result), Some(1));
+
+        clock.advance(Duration::seconds(1));
+        service.login_user(USERNAME, PASSWORD, None).await.unwrap();
+        // 成功するとユーザー名ごとの失敗の記録はリセットされる
+        let result = service.login_user(USERNAME, "wrong", None).await;
+        assert!(matches!(result, Err(AppError::Unauthorized)));
+    }
+
+    #[actix_rt::test]
+    async fn successful_logins_do_not_count_against_remote_ip() {
+        let clock = clock();
+        let service = auth_service(repository_with_session(clock.now()), clock);
+
+        for _ in 0..5 {
+            service
+                .login_user(USERNAME, PASSWORD, Some(REMOTE_IP))
+                .await
+                .unwrap();
+        }
+        for _ in 0..4 {
+            let result = service.login_user("unknown", "wrong", Some(REMOTE_IP)).await;
+            assert!(matches!(result, Err(AppError::Unauthorized)));
+        }
+        let result = service.login_user(USERNAME, PASSWORD, Some(REMOTE_IP)).await;
+        assert_eq!(retry_after(result), Some(1));
+    }
+
+    #[actix_rt::test]
+    async fn password_reset_token_is_stored_as_hash() {
+        let clock = clock();
+        let repository = repository_with_session(clock.now());
+        let service = auth_service(repository.clone(), clock);
+
+        let issued = service.issue_password_reset_token(1).await.unwrap();
+        let stored = repository.data().password_reset_tokens[0]
+            .token_hash
+            .clone();
+        assert_eq!(stored, hash_token(&issued.token));
+        assert_ne!(stored, issued.token);
+        assert!(repository.data().sessions.is_empty());
+
+        // 保存されたハッシュ値そのものはトークンとして使用できない
+        let result = service.reset_password(&stored, "new-password").await;
+        assert!(matches!(result, Err(AppError::Unauthorized)));
+        service
+            .reset_password(&issued.token, "new-password")
+            .await
+            .unwrap();
+        let result = service.reset_password(&issued.token, "new-password").await;
+        assert!(matches!(result, Err(AppError::Unauthorized)));
+    }
+
+    #[test]
+    fn hashes_token_with_sha256() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn logout_is_idempotent() {
+        let clock = clock();
+        let repository = repository_with_session(clock.now());
+        let service = auth_service(repository.clone(), clock);
+
+        service.logout_user(SESSION_TOKEN).await.unwrap();
+        service.logout_user(SESSION_TOKEN).await.unwrap();
+        service.logout_user("unknown-token").await.unwrap();
+
+        assert!(repository.data().sessions.is_empty());
+    }
+}
@@ -1,5 +1,8 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::models::user::Session;
+
 // 入力データ構造
 
 /// ユーザー登録リクエストのデータ構造
@@ -24,6 +27,20 @@ pub struct LogoutRequestDto {
     pub session_token: String,
 }
 
+/// パスワード変更リクエストのデータ構造
+#[derive(Deserialize)]
+pub struct ChangePasswordRequestDto {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+/// パスワード再設定リクエストのデータ構造
+#[derive(Deserialize)]
+pub struct ResetPasswordRequestDto {
+    pub token: String,
+    pub new_password: String,
+}
+
 // 出力データ構造
 
 /// ユーザーログインレスポンスのデータ構造
@@ -35,4 +52,48 @@ pub struct LoginResponseDto {
     pub role: String,
     pub dispatcher_id: Option<i32>,
     pub area_id: Option<i32>,
-}
\ No newline at end of file
+}
+
+/// セッション更新レスポンスのデータ構造
+#[derive(Serialize)]
+pub struct RefreshSessionResponseDto {
+    pub session_token: String,
+}
+
+/// セッションのデータ構造
+///
+/// セッショントークンは含まない
+#[derive(Serialize)]
+pub struct SessionDto {
+    pub id: i32,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// リクエストに使用したセッションかどうか
+    pub current: bool,
+}
+
+impl SessionDto {
+    /// Session エンティティから SessionDto を生成する関数
+    pub fn from_entity(entity: Session, current_session_id: i32) -> Self {
+        SessionDto {
+            id: entity.id,
+            created_at: entity.created_at,
+            last_seen_at: entity.last_seen_at,
+            current: entity.id == current_session_id,
+        }
+    }
+}
+
+/// セッション無効化レスポンスのデータ構造
+#[derive(Serialize)]
+pub struct RevokeSessionsResponseDto {
+    pub revoked: u64,
+}
+
+/// パスワードリセット用のトークンのデータ構造
+#[derive(Serialize)]
+pub struct PasswordResetTokenDto {
+    pub user_id: i32,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
@@ -8,7 +8,8 @@ use crate::models::order::CompletedOrder;
 /// クライアント注文リクエストのデータ構造
 #[derive(Deserialize, Debug)]
 pub struct ClientOrderRequestDto {
-    pub client_id: i32,
+    /// 省略した場合はセッションのユーザーIDを使用する
+    pub client_id: Option<i32>,
     pub node_id: i32,
     pub car_value: f64,
 }
@@ -17,7 +18,8 @@ pub struct ClientOrderRequestDto {
 #[derive(Deserialize, Debug)]
 pub struct DispatcherOrderRequestDto {
     pub order_id: i32,
-    pub dispatcher_id: i32,
+    /// 省略した場合はセッションのディスパッチャーIDを使用する
+    pub dispatcher_id: Option<i32>,
     pub tow_truck_id: i32,
     pub order_time: DateTime<Utc>,
 }
@@ -29,6 +31,19 @@ pub struct UpdateOrderStatusRequestDto {
     pub status: String,
 }
 
+/// 注文完了リクエストのデータ構造
+#[derive(Deserialize, Debug)]
+pub struct CompleteOrderRequestDto {
+    pub order_id: i32,
+}
+
+/// 注文キャンセルリクエストのデータ構造
+#[derive(Deserialize, Debug)]
+pub struct CancelOrderRequestDto {
+    pub order_id: i32,
+    pub reason: String,
+}
+
 // 出力データ構造
 
 /// 注文のデータ構造
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 
 // 入力データ構造
@@ -34,4 +35,178 @@ impl TowTruckDto {
             area_id: entity.area_id,
         }
     }
-}
\ No newline at end of file
+}
+
+/// 注文地点までの距離と到着予定時刻を含むレッカー車のデータ構造
+#[derive(Serialize, Clone)]
+pub struct NearestTowTruckDto {
+    #[serde(flatten)]
+    pub tow_truck: TowTruckDto,
+    pub distance: i32,
+    pub estimated_travel_seconds: i64,
+    pub estimated_arrival_time: DateTime<Utc>,
+}
+
+impl NearestTowTruckDto {
+    /// TowTruck エンティティと注文地点までの距離から NearestTowTruckDto を生成する関数
+    ///
+    /// `speed` - レッカー車が1秒あたりに進むグラフ上の距離
+    /// `now` - 到着予定時刻の基準となる現在時刻
+    pub fn from_entity(
+        entity: crate::models::tow_truck::TowTruck,
+        distance: i32,
+        speed: f64,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let estimated_travel_seconds = (distance as f64 / speed).ceil() as i64;
+        NearestTowTruckDto {
+            tow_truck: TowTruckDto::from_entity(entity),
+            distance,
+            estimated_travel_seconds,
+            estimated_arrival_time: now + Duration::seconds(estimated_travel_seconds),
+        }
+    }
+}
+
+/// レッカー車から注文地点までの経路のデータ構造
+#[derive(Serialize)]
+pub struct TowTruckRouteDto {
+    pub tow_truck_id: i32,
+    pub order_id: i32,
+    pub node_ids: Vec<i32>,
+    pub weights: Vec<i32>,
+    pub distance: i32,
+}
+
+impl TowTruckRouteDto {
+    /// Route から TowTruckRouteDto を生成する関数
+    pub fn from_route(tow_truck_id: i32, order_id: i32, route: crate::models::graph::Route) -> Self {
+        TowTruckRouteDto {
+            tow_truck_id,
+            order_id,
+            node_ids: route.node_ids,
+            weights: route.weights,
+            distance: route.cost,
+        }
+    }
+}
+
+/// レッカー車が訪れたノードのデータ構造
+///
+/// `cost_from_previous` - 直前のノードからのグラフ上の最短距離（最初のノードや到達できない場合は `None`）
+/// `implausible` - 記録時に経過時間内に到達できない移動と判定されたかどうか
+#[derive(Serialize)]
+pub struct LocationVisitDto {
+    pub node_id: i32,
+    pub x: i32,
+    pub y: i32,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub cost_from_previous: Option<i32>,
+    pub implausible: bool,
+}
+
+/// レッカー車の位置の履歴のデータ構造
+///
+/// `total_cost` - グラフ上で移動したコストの合計（到達できない区間は含まない）
+/// `unreachable_segments` - グラフ上で到達できない区間の数
+/// `truncated` - 取得できる件数の上限を超えたため、以降の履歴を省略したかどうか
+#[derive(Serialize)]
+pub struct LocationHistoryDto {
+    pub tow_truck_id: i32,
+    pub visits: Vec<LocationVisitDto>,
+    pub total_cost: i64,
+    pub unreachable_segments: usize,
+    pub truncated: bool,
+}
+
+impl LocationHistoryDto {
+    /// 古い順の位置の履歴から LocationHistoryDto を生成する関数
+    ///
+    /// `truncated` - 以降の履歴を省略したかどうか
+    /// `cost` - 2つのノード間のコストを返す関数（到達できない場合は `None`）
+    pub fn from_records(
+        tow_truck_id: i32,
+        records: Vec<crate::models::tow_truck::LocationRecord>,
+        truncated: bool,
+        mut cost: impl FnMut(i32, i32) -> Option<i32>,
+    ) -> Self {
+        let mut visits: Vec<LocationVisitDto> = Vec::with_capacity(records.len());
+        let mut total_cost = 0;
+        let mut unreachable_segments = 0;
+        for record in records {
+            let cost_from_previous = match visits.last() {
+                Some(previous) => {
+                    let segment_cost = cost(previous.node_id, record.node_id);
+                    match segment_cost {
+                        Some(segment_cost) => total_cost += segment_cost as i64,
+                        None => unreachable_segments += 1,
+                    }
+                    segment_cost
+                }
+                None => None,
+            };
+            visits.push(LocationVisitDto {
+                node_id: record.node_id,
+                x: record.x,
+                y: record.y,
+                timestamp: record.timestamp,
+                cost_from_previous,
+                implausible: record.implausible,
+            });
+        }
+
+        LocationHistoryDto {
+            tow_truck_id,
+            visits,
+            total_cost,
+            unreachable_segments,
+            truncated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tow_truck::LocationRecord;
+
+    fn record(node_id: i32) -> LocationRecord {
+        LocationRecord {
+            node_id,
+            x: node_id * 10,
+            y: node_id * 20,
+            timestamp: None,
+            implausible: false,
+        }
+    }
+
+    #[test]
+    fn sums_costs_between_consecutive_visits() {
+        // ノード 3 へはどこからも到達できない
+        let cost = |from: i32, to: i32| match (from, to) {
+            (_, 3) | (3, _) => None,
+            (from, to) => Some((from - to).abs() * 5),
+        };
+        let history = LocationHistoryDto::from_records(
+            1,
+            vec![record(1), record(2), record(2), record(3), record(4), record(1)],
+            false,
+            cost,
+        );
+
+        let costs: Vec<_> = history.visits.iter().map(|visit| visit.cost_from_previous).collect();
+        assert_eq!(costs, vec![None, Some(5), Some(0), None, None, Some(15)]);
+        assert_eq!(history.total_cost, 20);
+        assert_eq!(history.unreachable_segments, 2);
+        assert_eq!((history.visits[1].x, history.visits[1].y), (20, 40));
+    }
+
+    #[test]
+    fn returns_empty_history_without_records() {
+        let history = LocationHistoryDto::from_records(1, Vec::new(), false, |_, _| Some(1));
+
+        assert!(history.visits.is_empty());
+        assert_eq!(history.total_cost, 0);
+        assert_eq!(history.unreachable_segments, 0);
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::errors::AppError;
+
+/// 保持する試行記録の数がこれを超えた場合、期限切れの記録を削除する
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// ログイン試行の制限の設定を表す構造体
+///
+/// `free_attempts` - 待ち時間なしで許可する連続失敗回数
+/// `lockout_attempts` - ロックアウトする連続失敗回数
+/// `backoff_base` - `free_attempts` を超えた最初の失敗後の待ち時間（以降、失敗ごとに2倍になる）
+/// `lockout_duration` - ロックアウトの期間（失敗の記録もこの期間が経過するとリセットされる）
+#[derive(Clone, Copy, Debug)]
+pub struct LoginThrottleConfig {
+    pub free_attempts: u32,
+    pub lockout_attempts: u32,
+    pub backoff_base: Duration,
+    pub lockout_duration: Duration,
+}
+
+/// キーごとのログイン試行の記録
+#[derive(Clone, Copy, Debug)]
+struct AttemptState {
+    failures: u32,
+    last_failure_at: DateTime<Utc>,
+    blocked_until: Option<DateTime<Utc>>,
+}
+
+/// ユーザー名やIPアドレスなどのキーごとにログインの連続失敗を数え、
+/// 指数的に増える待ち時間と一時的なロックアウトを課す構造体
+#[derive(Debug)]
+pub struct LoginThrottle {
+    config: LoginThrottleConfig,
+    attempts: Mutex<HashMap<String, AttemptState>>,
+}
+
+impl LoginThrottle {
+    /// 新しいログイン試行の制限を作成する
+    pub fn new(config: LoginThrottleConfig) -> Self {
+        LoginThrottle {
+            config,
+            attempts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// キーのログイン試行を開始する
+    ///
+    /// 試行が許可されているかの確認と試行の記録を1つのロックの中で行うため、
+    /// 並行した試行がまとめて確認を通過することはない。
+    /// 試行はパスワードの検証前に失敗として記録し、成功した場合は `record_success` または
+    /// `forgive_attempt` で取り消す。
+    /// 待ち時間中またはロックアウト中の場合は記録せずに `AppError::TooManyLoginAttempts` を返す
+    pub fn begin_attempt(&self, key: &str, now: DateTime<Utc>) -> Result<(), AppError> {
+        let mut attempts = self.attempts.lock().unwrap_or_else(PoisonError::into_inner);
+        check(attempts.get(key), now)?;
+        self.record_failure(&mut attempts, key, now);
+        Ok(())
+    }
+
+    /// キーのログイン成功を記録し、失敗の記録をリセットする
+    pub fn record_success(&self, key: &str) {
+        self.attempts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+    }
+
+    /// `begin_attempt` で失敗として記録した試行を1回分取り消す
+    ///
+    /// 他の失敗を隠せないよう、`record_success` と異なり記録全体はリセットしない
+    pub fn forgive_attempt(&self, key: &str) {
+        let mut attempts = self.attempts.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(state) = attempts.get_mut(key) {
+            state.failures = state.failures.saturating_sub(1);
+            state.blocked_until = self
+                .delay_after(state.failures)
+                .map(|delay| state.last_failure_at + delay);
+        }
+    }
+
+    /// キーのログイン失敗を記録する
+    fn record_failure(
+        &self,
+        attempts: &mut HashMap<String, AttemptState>,
+        key: &str,
+        now: DateTime<Utc>,
+    ) {
+        if attempts.len() >= PRUNE_THRESHOLD {
+            attempts.retain(|_, state| !self.is_stale(state, now));
+        }
+
+        let state = attempts.entry(key.to_string()).or_insert(AttemptState {
+            failures: 0,
+            last_failure_at: now,
+            blocked_until: None,
+        });
+        if self.is_stale(state, now) {
+            state.failures = 0;
+        }
+        state.failures += 1;
+        state.last_failure_at = now;
+        state.blocked_until = self.delay_after(state.failures).map(|delay| now + delay);
+    }
+
+    /// 連続失敗回数に応じた待ち時間を返す
+    fn delay_after(&self, failures: u32) -> Option<Duration> {
+        if failures >= self.config.lockout_attempts {
+            return Some(self.config.lockout_duration);
+        }
+        if failures <= self.config.free_attempts {
+            return None;
+        }
+
+        // 2^30 倍で十分にロックアウトの期間を超えるため、それ以上はシフトしない
+        let exponent = (failures - self.config.free_attempts - 1).min(30);
+        let delay = self.config.backoff_base * (1 << exponent);
+        Some(delay.min(self.config.lockout_duration))
+    }
+
+    /// 最後の失敗からロックアウトの期間が経過し、待ち時間も終わった記録かどうかを返す
+    fn is_stale(&self, state: &AttemptState, now: DateTime<Utc>) -> bool {
+        let is_blocked = matches!(state.blocked_until, Some(blocked_until) if blocked_until > now);
+        now - state.last_failure_at >= self.config.lockout_duration && !is_blocked
+    }
+}
+
+/// キーのログイン試行が許可されているかを確認する
+///
+/// 待ち時間中またはロックアウト中の場合は `AppError::TooManyLoginAttempts` を返す
+fn check(state: Option<&AttemptState>, now: DateTime<Utc>) -> Result<(), AppError> {
+    match state.and_then(|state| state.blocked_until) {
+        Some(blocked_until) if blocked_until > now => Err(AppError::TooManyLoginAttempts {
+            retry_after_seconds: retry_after_seconds(blocked_until - now),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// 待ち時間を切り上げた秒数に変換する
+fn retry_after_seconds(remaining: Duration) -> i64 {
+    let seconds = remaining.num_seconds();
+    if remaining > Duration::seconds(seconds) {
+        seconds + 1
+    } else {
+        seconds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::domains::clock::{Clock, FakeClock};
+
+    fn clock() -> FakeClock {
+        FakeClock::new(Utc.with_ymd_and_hms(2024, 7, 25, 0, 0, 0).unwrap())
+    }
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle::new(LoginThrottleConfig {
+            free_attempts: 3,
+            lockout_attempts: 7,
+            backoff_base: Duration::seconds(2),
+            lockout_duration: Duration::minutes(15),
+        })
+    }
+
+    /// 試行を記録せずに、キーの待ち時間の秒数を返す（待ち時間中でない場合は `None`）
+    fn blocked_for(throttle: &LoginThrottle, key: &str, now: DateTime<Utc>) -> Option<i64> {
+        retry_after(check(throttle.attempts.lock().unwrap().get(key), now))
+    }
+
+    /// 待ち時間中かどうかに関わらず、キーのログイン失敗を記録する
+    fn fail(throttle: &LoginThrottle, key: &str, now: DateTime<Utc>) {
+        throttle.record_failure(&mut throttle.attempts.lock().unwrap(), key, now);
+    }
+
+    fn retry_after(result: Result<(), AppError>) -> Option<i64> {
+        match result {
+            Ok(()) => None,
+            Err(AppError::TooManyLoginAttempts {
+                retry_after_seconds,
+            }) => Some(retry_after_seconds),
+            Err(err) => panic!("unexpected error: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn allows_free_attempts_without_delay() {
+        let clock = clock();
+        let throttle = throttle();
+
+        for _ in 0..3 {
+            assert_eq!(blocked_for(&throttle, "alice", clock.now()), None);
+            fail(&throttle, "alice", clock.now());
+        }
+        assert_eq!(blocked_for(&throttle, "alice", clock.now()), None);
+    }
+
+    #[test]
+    fn doubles_delay_after_each_failure() {
+        let clock = clock();
+        let throttle = throttle();
+        for _ in 0..3 {
+            fail(&throttle, "alice", clock.now());
+        }
+
+        for expected in [2, 4, 8] {
+            fail(&throttle, "alice", clock.now());
+            assert_eq!(blocked_for(&throttle, "alice", clock.now()), Some(expected));
+
+            clock.advance(Duration::seconds(expected - 1));
+            assert_eq!(blocked_for(&throttle, "alice", clock.now()), Some(1));
+            clock.advance(Duration::seconds(1));
+            assert_eq!(blocked_for(&throttle, "alice", clock.now()), None);
+        }
+    }
+
+    #[test]
+    fn locks_out_until_lockout_duration_passes() {
+        let clock = clock();
+        let throttle = throttle();
+        for _ in 0..7 {
+            fail(&throttle, "alice", clock.now());
+        }
+        assert_eq!(blocked_for(&throttle, "alice", clock.now()), Some(15 * 60));
+
+        clock.advance(Duration::minutes(15) - Duration::milliseconds(500));
+        assert_eq!(blocked_for(&throttle, "alice", clock.now()), Some(1));
+
+        clock.advance(Duration::milliseconds(500));
+        assert_eq!(blocked_for(&throttle, "alice", clock.now()), None);
+    }
+
+    #[test]
+    fn forgets_failures_after_lockout_duration() {
+        let clock = clock();
+        let throttle = throttle();
+        for _ in 0..3 {
+            fail(&throttle, "alice", clock.now());
+        }
+
+        clock.advance(Duration::minutes(15));
+        fail(&throttle, "alice", clock.now());
+        assert_eq!(blocked_for(&throttle, "alice", clock.now()), None);
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let clock = clock();
+        let throttle = throttle();
+        for _ in 0..4 {
+            fail(&throttle, "alice", clock.now());
+        }
+        throttle.record_success("alice");
+
+        assert_eq!(blocked_for(&throttle, "alice", clock.now()), None);
+        fail(&throttle, "alice", clock.now());
+        assert_eq!(blocked_for(&throttle, "alice", clock.now()), None);
+    }
+
+    #[test]
+    fn tracks_keys_independently() {
+        let clock = clock();
+        let throttle = throttle();
+        for _ in 0..6 {
+            fail(&throttle, "alice", clock.now());
+        }
+
+        assert!(blocked_for(&throttle, "alice", clock.now()).is_some());
+        assert_eq!(blocked_for(&throttle, "bob", clock.now()), None);
+    }
+
+    #[test]
+    fn begin_attempt_counts_attempt_as_failure() {
+        let clock = clock();
+        let throttle = throttle();
+
+        for _ in 0..4 {
+            throttle.begin_attempt("alice", clock.now()).unwrap();
+        }
+
+        assert_eq!(
+            retry_after(throttle.begin_attempt("alice", clock.now())),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn parallel_attempts_cannot_all_pass_check() {
+        let clock = clock();
+        let now = clock.now();
+        let throttle = throttle();
+
+        let allowed = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..10)
+                .map(|_| scope.spawn(|| throttle.begin_attempt("alice", now).is_ok()))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .filter(|allowed| *allowed)
+                .count()
+        });
+
+        assert_eq!(allowed, 4);
+    }
+
+    #[test]
+    fn forgive_attempt_removes_single_attempt() {
+        let clock = clock();
+        let throttle = throttle();
+        for _ in 0..4 {
+            throttle.begin_attempt("alice", clock.now()).unwrap();
+        }
+
+        throttle.forgive_attempt("alice");
+        assert_eq!(blocked_for(&throttle, "alice", clock.now()), None);
+
+        throttle.begin_attempt("alice", clock.now()).unwrap();
+        assert_eq!(blocked_for(&throttle, "alice", clock.now()), Some(2));
+    }
+}
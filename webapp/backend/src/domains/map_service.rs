@@ -1,8 +1,16 @@
+use std::sync::Arc;
+
 use crate::{
-    errors::AppError,
-    models::graph::{Edge, Node},
+    errors::{AppError, FieldError},
+    models::graph::{Edge, GraphCache, Node},
+    models::user::AuthenticatedUser,
 };
 
+/// エッジの重みの上限
+///
+/// 経路のコストの合計が `i32` の範囲に収まるよう、現実的な値を大きく超える重みは受け付けない
+const MAX_EDGE_WEIGHT: i32 = 1_000_000;
+
 /// マップリポジトリのトレイト
 pub trait MapRepository {
     /// 全てのノードを取得する
@@ -45,31 +53,174 @@ pub trait MapRepository {
 #[derive(Debug)]
 pub struct MapService<T: MapRepository + std::fmt::Debug> {
     repository: T,
+    graph_cache: Arc<GraphCache>,
 }
 
 impl<T: MapRepository + std::fmt::Debug> MapService<T> {
     /// 新しいマップサービスを作成する
-    pub fn new(repository: T) -> Self {
-        MapService { repository }
+    pub fn new(repository: T, graph_cache: Arc<GraphCache>) -> Self {
+        MapService {
+            repository,
+            graph_cache,
+        }
     }
 
     /// エッジを更新する
     /// 
+    /// `user` - エッジを更新する認証済みのユーザー
     /// `node_a_id` - ノードAのID
     /// `node_b_id` - ノードBのID
     /// `weight` - 新しい重み
     /// 
+    /// 重みが負、または `MAX_EDGE_WEIGHT` を超える場合は `AppError::ValidationFailed` を返す。
+    /// 両端のノードがディスパッチャーの担当エリアにある場合のみ更新でき、それ以外の場合は
+    /// `AppError::Forbidden` を返す。
+    /// データベースの更新後、キャッシュ済みのエリアのグラフも同じ重みに更新する
+    /// 
     /// 戻り値: 成功した場合は空のResult、失敗した場合はAppError
     pub async fn update_edge(
         &self,
+        user: &AuthenticatedUser,
         node_a_id: i32,
         node_b_id: i32,
         weight: i32,
     ) -> Result<(), AppError> {
+        if !(0..=MAX_EDGE_WEIGHT).contains(&weight) {
+            return Err(AppError::ValidationFailed(vec![FieldError::new(
+                "weight",
+                format!("weight must be between 0 and {}", MAX_EDGE_WEIGHT),
+            )]));
+        }
+
+        let area_id = user.area_id().ok_or(AppError::Forbidden)?;
+        for node_id in [node_a_id, node_b_id] {
+            if self.repository.get_area_id_by_node_id(node_id).await? != area_id {
+                return Err(AppError::Forbidden);
+            }
+        }
+
         self.repository
             .update_edge(node_a_id, node_b_id, weight)
             .await?;
 
+        self.graph_cache
+            .update_edge_weight(area_id, node_a_id, node_b_id, weight);
+
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::{Dispatcher, Role, User};
+    use crate::repositories::in_memory::InMemoryRepository;
+
+    const AREA_1_DISPATCHER: i32 = 1;
+    const AREA_2_DISPATCHER: i32 = 2;
+
+    /// エリア1にノード1と2、エリア2にノード3を用意し、全てのノードの間にエッジを用意する
+    fn map_service() -> (MapService<InMemoryRepository>, InMemoryRepository) {
+        let repository = InMemoryRepository::default();
+        {
+            let mut data = repository.data();
+            for (area_id, node_id) in [(1, 1), (1, 2), (2, 3)] {
+                data.nodes.push((
+                    area_id,
+                    Node {
+                        id: node_id,
+                        x: 0,
+                        y: 0,
+                    },
+                ));
+            }
+            for (node_a_id, node_b_id) in [(1, 2), (2, 1), (2, 3), (3, 2)] {
+                data.edges.push(Edge {
+                    node_a_id,
+                    node_b_id,
+                    weight: 10,
+                });
+            }
+            for (user_id, area_id) in [(AREA_1_DISPATCHER, 1), (AREA_2_DISPATCHER, 2)] {
+                data.users.push(User {
+                    id: user_id,
+                    username: format!("dispatcher{}", user_id),
+                    password: String::new(),
+                    role: Role::Dispatcher.as_str().to_string(),
+                });
+                data.dispatchers.push(Dispatcher {
+                    id: user_id,
+                    user_id,
+                    area_id,
+                });
+            }
+        }
+
+        let service = MapService::new(repository.clone(), Arc::new(GraphCache::new(None)));
+        (service, repository)
+    }
+
+    fn weight(repository: &InMemoryRepository, node_a_id: i32, node_b_id: i32) -> i32 {
+        repository
+            .data()
+            .edges
+            .iter()
+            .find(|edge| (edge.node_a_id, edge.node_b_id) == (node_a_id, node_b_id))
+            .map(|edge| edge.weight)
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn dispatcher_can_update_edge_in_own_area() {
+        let (service, repository) = map_service();
+        let dispatcher = repository.authenticated_user(AREA_1_DISPATCHER);
+
+        service.update_edge(&dispatcher, 1, 2, 30).await.unwrap();
+
+        assert_eq!(weight(&repository, 1, 2), 30);
+        assert_eq!(weight(&repository, 2, 1), 30);
+    }
+
+    #[actix_rt::test]
+    async fn dispatcher_cannot_update_edge_outside_own_area() {
+        let (service, repository) = map_service();
+        let dispatcher = repository.authenticated_user(AREA_2_DISPATCHER);
+
+        // 両端がエリア1のエッジと、エリアをまたぐエッジ
+        for (node_a_id, node_b_id) in [(1, 2), (3, 2)] {
+            let result = service
+                .update_edge(&dispatcher, node_a_id, node_b_id, 30)
+                .await;
+
+            assert!(
+                matches!(result, Err(AppError::Forbidden)),
+                "{} - {}",
+                node_a_id,
+                node_b_id
+            );
+            assert_eq!(weight(&repository, node_a_id, node_b_id), 10);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn rejects_negative_or_too_large_weight() {
+        let (service, repository) = map_service();
+        let dispatcher = repository.authenticated_user(AREA_1_DISPATCHER);
+
+        for weight_value in [-1, MAX_EDGE_WEIGHT + 1] {
+            let result = service.update_edge(&dispatcher, 1, 2, weight_value).await;
+
+            assert!(
+                matches!(result, Err(AppError::ValidationFailed(_))),
+                "{}",
+                weight_value
+            );
+            assert_eq!(weight(&repository, 1, 2), 10);
+        }
+        service
+            .update_edge(&dispatcher, 1, 2, MAX_EDGE_WEIGHT)
+            .await
+            .unwrap();
+        assert_eq!(weight(&repository, 1, 2), MAX_EDGE_WEIGHT);
+    }
+}
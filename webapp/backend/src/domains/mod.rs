@@ -1,5 +1,9 @@
 pub mod auth_service;
+pub mod clock;
 pub mod dto;
+pub mod login_throttle;
 pub mod map_service;
 pub mod order_service;
+pub mod pagination;
+pub mod password_policy;
 pub mod tow_truck_service;
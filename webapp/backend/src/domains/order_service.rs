@@ -1,15 +1,18 @@
 use chrono::{DateTime, Utc};
+use log::error;
 
 use super::{
     auth_service::AuthRepository,
     dto::order::{CompletedOrderDto, OrderDto},
     map_service::MapRepository,
+    pagination::validate_pagination,
     tow_truck_service::TowTruckRepository,
 };
 
 use crate::{
     errors::AppError,
-    models::order::{CompletedOrder, Order},
+    models::order::{CancelReason, CompletedOrder, Order, OrderStatus},
+    models::user::{AuthenticatedUser, Role},
 };
 
 /// 注文リポジトリのトレイト
@@ -17,8 +20,16 @@ pub trait OrderRepository {
     /// 注文IDに基づいて注文を取得する
     async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError>;
 
-    /// 注文のステータスを更新する
-    async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError>;
+    /// 注文のステータスを更新し、遷移履歴を記録する
+    ///
+    /// 注文のステータスが `from_status` の場合のみ更新する
+    async fn update_order_status(
+        &self,
+        order_id: i32,
+        from_status: OrderStatus,
+        to_status: OrderStatus,
+        changed_by: i32,
+    ) -> Result<(), AppError>;
 
     /// ページネーションされた注文リストを取得する
     async fn get_paginated_orders(
@@ -39,23 +50,44 @@ pub trait OrderRepository {
         car_value: f64,
     ) -> Result<(), AppError>;
 
-    /// 注文のディスパッチ情報を更新する
-    async fn update_order_dispatched(
+    /// 注文にレッカー車を割り当てる
+    ///
+    /// 完了した注文の作成、注文のディスパッチ情報の更新、レッカー車のステータス更新、
+    /// ステータスの遷移履歴の記録を1つのトランザクションで行う
+    async fn dispatch_order(
         &self,
-        id: i32,
+        order_id: i32,
         dispatcher_id: i32,
         tow_truck_id: i32,
+        completed_time: DateTime<Utc>,
     ) -> Result<(), AppError>;
 
-    /// 完了した注文を作成する
-    async fn create_completed_order(
+    /// 注文を完了する
+    ///
+    /// 注文のステータスと完了時間の更新、完了した注文の完了時間の更新、レッカー車のステータス更新、
+    /// ステータスの遷移履歴の記録を1つのトランザクションで行う
+    async fn complete_order(
         &self,
         order_id: i32,
-        tow_truck_id: i32,
+        completed_by: i32,
         completed_time: DateTime<Utc>,
     ) -> Result<(), AppError>;
 
+    /// 注文をキャンセルする
+    ///
+    /// 注文のステータスが `from_status` の場合のみ、注文のステータス更新、キャンセル理由の記録、
+    /// 割り当てられていたレッカー車の解放、ステータスの遷移履歴の記録を1つのトランザクションで行う
+    async fn cancel_order(
+        &self,
+        order_id: i32,
+        from_status: OrderStatus,
+        reason: CancelReason,
+        cancelled_by: i32,
+    ) -> Result<(), AppError>;
+
     /// 全ての完了した注文を取得する
+    ///
+    /// キャンセルされた注文は含まない
     async fn get_all_completed_orders(&self) -> Result<Vec<CompletedOrder>, AppError>;
 }
 
@@ -96,79 +128,264 @@ impl<
     }
 
     /// 注文のステータスを更新する
-    pub async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError> {
+    ///
+    /// `order_id` - 注文ID
+    /// `status` - 新しいステータス
+    /// `user` - ステータスを変更する認証済みのユーザー
+    ///
+    /// 注文に割り当てられたレッカー車のドライバーと、注文と同じエリアのディスパッチャー以外の場合は
+    /// `AppError::Forbidden` を、現在のステータスから遷移できないステータスが指定された場合は
+    /// `AppError::InvalidOrderStatusTransition` を返す。
+    /// `dispatched` への遷移はレッカー車の割り当てを伴うため、ディスパッチャー注文の作成でのみ行い、
+    /// `cancelled` への遷移はキャンセル理由と権限の確認を伴うため、`cancel_order` でのみ行う。
+    /// `completed` への遷移は `complete_order` と同様にレッカー車の解放を伴う
+    pub async fn update_order_status(
+        &self,
+        order_id: i32,
+        status: &str,
+        user: &AuthenticatedUser,
+    ) -> Result<(), AppError> {
+        let next_status: OrderStatus = status.parse()?;
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        if !self
+            .is_assigned_driver_or_area_dispatcher(&order, user)
+            .await?
+        {
+            return Err(AppError::Forbidden);
+        }
+        let current_status: OrderStatus = order.status.parse()?;
+
+        if matches!(next_status, OrderStatus::Dispatched | OrderStatus::Cancelled)
+            || !current_status.can_transition_to(next_status)
+        {
+            return Err(AppError::InvalidOrderStatusTransition {
+                from: current_status,
+                to: next_status,
+            });
+        }
+
+        if next_status == OrderStatus::Completed {
+            return self
+                .order_repository
+                .complete_order(order_id, user.user.id, Utc::now())
+                .await;
+        }
         self.order_repository
-            .update_order_status(order_id, status)
+            .update_order_status(order_id, current_status, next_status, user.user.id)
             .await
     }
 
+    /// 注文を完了する
+    ///
+    /// `order_id` - 注文ID
+    /// `user` - 注文を完了する認証済みのユーザー
+    ///
+    /// 注文を `completed` にして完了時間を記録し、割り当てられていたレッカー車を `available` に戻す。
+    /// 結果取得で報告される完了した注文の完了時間は、割り当て時に記録したものから変更しない。
+    /// 注文に割り当てられたレッカー車のドライバーと、注文と同じエリアのディスパッチャー以外の場合は
+    /// `AppError::Forbidden` を、注文が `towing` 以外の場合は `AppError::InvalidOrderStatusTransition` を返す
+    pub async fn complete_order(
+        &self,
+        order_id: i32,
+        user: &AuthenticatedUser,
+    ) -> Result<(), AppError> {
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        if !self
+            .is_assigned_driver_or_area_dispatcher(&order, user)
+            .await?
+        {
+            return Err(AppError::Forbidden);
+        }
+
+        self.order_repository
+            .complete_order(order_id, user.user.id, Utc::now())
+            .await
+    }
+
+    /// 注文をキャンセルする
+    ///
+    /// `order_id` - 注文ID
+    /// `reason` - キャンセル理由
+    /// `user` - 注文をキャンセルする認証済みのユーザー
+    ///
+    /// 注文を作成したクライアントはレッカー車の割り当て前（`pending`）のみ、
+    /// 注文と同じエリアのディスパッチャーは完了前であればいつでもキャンセルできる。
+    /// それ以外のユーザーの場合は `AppError::Forbidden` を返す。
+    /// 割り当てられていたレッカー車は `available` に戻り、キャンセルされた注文は結果取得に含まれない
+    pub async fn cancel_order(
+        &self,
+        order_id: i32,
+        reason: &str,
+        user: &AuthenticatedUser,
+    ) -> Result<(), AppError> {
+        let reason: CancelReason = reason.parse()?;
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let current_status: OrderStatus = order.status.parse()?;
+
+        let is_allowed = match user.user.role.parse::<Role>() {
+            Ok(Role::Client) => {
+                order.client_id == user.user.id && current_status == OrderStatus::Pending
+            }
+            Ok(Role::Dispatcher) => self.is_area_dispatcher(&order, user).await?,
+            _ => false,
+        };
+        if !is_allowed {
+            return Err(AppError::Forbidden);
+        }
+
+        if !current_status.can_transition_to(OrderStatus::Cancelled) {
+            return Err(AppError::InvalidOrderStatusTransition {
+                from: current_status,
+                to: OrderStatus::Cancelled,
+            });
+        }
+
+        self.order_repository
+            .cancel_order(order_id, current_status, reason, user.user.id)
+            .await
+    }
+
+    /// ユーザーが注文と同じエリアのディスパッチャーかどうかを返す
+    async fn is_area_dispatcher(
+        &self,
+        order: &Order,
+        user: &AuthenticatedUser,
+    ) -> Result<bool, AppError> {
+        let Some(area_id) = user.area_id() else {
+            return Ok(false);
+        };
+        let order_area_id = self
+            .map_repository
+            .get_area_id_by_node_id(order.node_id)
+            .await?;
+        Ok(area_id == order_area_id)
+    }
+
+    /// ユーザーが注文に割り当てられたレッカー車のドライバー、
+    /// または注文と同じエリアのディスパッチャーかどうかを返す
+    async fn is_assigned_driver_or_area_dispatcher(
+        &self,
+        order: &Order,
+        user: &AuthenticatedUser,
+    ) -> Result<bool, AppError> {
+        match user.user.role.parse::<Role>() {
+            Ok(Role::Driver) => {
+                let Some(tow_truck_id) = order.tow_truck_id else {
+                    return Ok(false);
+                };
+                let tow_truck = self
+                    .tow_truck_repository
+                    .find_tow_truck_by_id(tow_truck_id)
+                    .await?;
+                Ok(tow_truck.is_some_and(|tow_truck| tow_truck.driver_id == user.user.id))
+            }
+            Ok(Role::Dispatcher) => self.is_area_dispatcher(order, user).await,
+            _ => Ok(false),
+        }
+    }
+
     /// 注文IDに基づいて注文情報を取得する
-    pub async fn get_order_by_id(&self, id: i32) -> Result<OrderDto, AppError> {
+    ///
+    /// `user` - 注文情報を取得する認証済みのユーザー
+    ///
+    /// 注文したクライアント、割り当てられたレッカー車のドライバー、注文と同じエリアのディスパッチャー以外は
+    /// `AppError::Forbidden` を返す
+    pub async fn get_order_by_id(
+        &self,
+        id: i32,
+        user: &AuthenticatedUser,
+    ) -> Result<OrderDto, AppError> {
         let order = self.order_repository.find_order_by_id(id).await?;
+        let is_allowed = order.client_id == user.user.id
+            || self
+                .is_assigned_driver_or_area_dispatcher(&order, user)
+                .await?;
+        if !is_allowed {
+            return Err(AppError::Forbidden);
+        }
+        self.to_order_dto(order).await
+    }
+
+    /// ページネーションされた注文リストを取得する
+    /// 
+    /// `page` または `page_size` が不正な場合は `AppError::ValidationFailed` を返す
+    /// 
+    /// ボトルネックになりうる箇所: データベースからの大量データ取得
+    /// - ページネーションとフィルタリングを適用することで、データベースからの取得負荷を軽減しています
+    pub async fn get_paginated_orders(
+        &self,
+        page: i32,
+        page_size: i32,
+        sort_by: Option<String>,
+        sort_order: Option<String>,
+        status: Option<String>,
+        area: Option<i32>,
+    ) -> Result<Vec<OrderDto>, AppError> {
+        validate_pagination(page, page_size)?;
+        let orders = self
+            .order_repository
+            .get_paginated_orders(page, page_size, sort_by, sort_order, status, area)
+            .await?;
+
+        let mut results = Vec::new();
+        for order in orders {
+            results.push(self.to_order_dto(order).await?);
+        }
+
+        Ok(results)
+    }
 
+    /// 注文に関連するユーザー名とエリアIDを取得し、注文情報に変換する
+    ///
+    /// 注文が参照するユーザーやノードが存在しない場合はデータの不整合としてログに記録し、
+    /// `AppError::InternalServerError` を返す
+    async fn to_order_dto(&self, order: Order) -> Result<OrderDto, AppError> {
         // クライアントのユーザー名を取得
-        let client_username = self
-            .auth_repository
-            .find_user_by_id(order.client_id)
-            .await
-            .unwrap()
-            .unwrap()
-            .username;
+        let client_username = self.find_username(order.id, order.client_id).await?;
 
         // ディスパッチャー情報を取得
         let dispatcher = match order.dispatcher_id {
-            Some(dispatcher_id) => self
-                .auth_repository
-                .find_dispatcher_by_id(dispatcher_id)
-                .await
-                .unwrap(),
+            Some(dispatcher_id) => {
+                self.auth_repository
+                    .find_dispatcher_by_id(dispatcher_id)
+                    .await?
+            }
             None => None,
         };
         let (dispatcher_user_id, dispatcher_username) = match dispatcher {
             Some(dispatcher) => (
                 Some(dispatcher.user_id),
-                Some(
-                    self.auth_repository
-                        .find_user_by_id(dispatcher.user_id)
-                        .await
-                        .unwrap()
-                        .unwrap()
-                        .username,
-                ),
+                Some(self.find_username(order.id, dispatcher.user_id).await?),
             ),
             None => (None, None),
         };
 
         // レッカー車情報を取得
         let tow_truck = match order.tow_truck_id {
-            Some(tow_truck_id) => self
-                .tow_truck_repository
-                .find_tow_truck_by_id(tow_truck_id)
-                .await
-                .unwrap(),
+            Some(tow_truck_id) => {
+                self.tow_truck_repository
+                    .find_tow_truck_by_id(tow_truck_id)
+                    .await?
+            }
             None => None,
         };
         let (driver_user_id, driver_username) = match tow_truck {
             Some(tow_truck) => (
                 Some(tow_truck.driver_id),
-                Some(
-                    self.auth_repository
-                        .find_user_by_id(tow_truck.driver_id)
-                        .await
-                        .unwrap()
-                        .unwrap()
-                        .username,
-                ),
+                Some(self.find_username(order.id, tow_truck.driver_id).await?),
             ),
             None => (None, None),
         };
 
         // ノードIDに基づいてエリアIDを取得
-        let area_id = self
-            .map_repository
-            .get_area_id_by_node_id(order.node_id)
-            .await
-            .unwrap();
+        let area_id = match self.map_repository.get_area_id_by_node_id(order.node_id).await {
+            Ok(area_id) => area_id,
+            Err(sqlx::Error::RowNotFound) => {
+                error!("Order {} references missing node {}", order.id, order.node_id);
+                return Err(AppError::InternalServerError);
+            }
+            Err(err) => return Err(err.into()),
+        };
 
         Ok(OrderDto {
             id: order.id,
@@ -189,107 +406,18 @@ impl<
         })
     }
 
-    /// ページネーションされた注文リストを取得する
-    /// 
-    /// ボトルネックになりうる箇所: データベースからの大量データ取得
-    /// - ページネーションとフィルタリングを適用することで、データベースからの取得負荷を軽減しています
-    pub async fn get_paginated_orders(
-        &self,
-        page: i32,
-        page_size: i32,
-        sort_by: Option<String>,
-        sort_order: Option<String>,
-        status: Option<String>,
-        area: Option<i32>,
-    ) -> Result<Vec<OrderDto>, AppError> {
-        let orders = self
-            .order_repository
-            .get_paginated_orders(page, page_size, sort_by, sort_order, status, area)
-            .await?;
-
-        let mut results = Vec::new();
-
-        for order in orders {
-            // クライアントのユーザー名を取得
-            let client_username = self
-                .auth_repository
-                .find_user_by_id(order.client_id)
-                .await
-                .unwrap()
-                .unwrap()
-                .username;
-
-            // ディスパッチャー情報を取得
-            let dispatcher = match order.dispatcher_id {
-                Some(dispatcher_id) => self
-                    .auth_repository
-                    .find_dispatcher_by_id(dispatcher_id)
-                    .await?,
-                None => None,
-            };
-            let (dispatcher_user_id, dispatcher_username) = match dispatcher {
-                Some(dispatcher) => (
-                    Some(dispatcher.user_id),
-                    Some(
-                        self.auth_repository
-                            .find_user_by_id(dispatcher.user_id)
-                            .await?
-                            .ok_or(AppError::NotFound)?
-                            .username,
-                    ),
-                ),
-                None => (None, None),
-            };
-
-            // レッカー車情報を取得
-            let tow_truck = match order.tow_truck_id {
-                Some(tow_truck_id) => self
-                    .tow_truck_repository
-                    .find_tow_truck_by_id(tow_truck_id)
-                    .await?,
-                None => None,
-            };
-            let (driver_user_id, driver_username) = match tow_truck {
-                Some(tow_truck) => (
-                    Some(tow_truck.driver_id),
-                    Some(
-                        self.auth_repository
-                            .find_user_by_id(tow_truck.driver_id)
-                            .await?
-                            .ok_or(AppError::NotFound)?
-                            .username,
-                    ),
-                ),
-                None => (None, None),
-            };
-
-            // ノードIDに基づいてエリアIDを取得
-            let order_area_id = self
-                .map_repository
-                .get_area_id_by_node_id(order.node_id)
-                .await
-                .unwrap();
-
-            results.push(OrderDto {
-                id: order.id,
-                client_id: order.client_id,
-                client_username: Some(client_username),
-                dispatcher_id: order.dispatcher_id,
-                dispatcher_user_id,
-                dispatcher_username,
-                tow_truck_id: order.tow_truck_id,
-                driver_user_id,
-                driver_username,
-                area_id: order_area_id,
-                status: order.status,
-                node_id: order.node_id,
-                car_value: order.car_value,
-                order_time: order.order_time,
-                completed_time: order.completed_time,
-            });
+    /// 注文が参照するユーザーのユーザー名を取得する
+    ///
+    /// `order_id` - ログに記録する注文ID
+    /// `user_id` - ユーザーID
+    async fn find_username(&self, order_id: i32, user_id: i32) -> Result<String, AppError> {
+        match self.auth_repository.find_user_by_id(user_id).await? {
+            Some(user) => Ok(user.username),
+            None => {
+                error!("Order {} references missing user {}", order_id, user_id);
+                Err(AppError::InternalServerError)
+            }
         }
-
-        Ok(results)
     }
 
     /// クライアント注文を作成する
@@ -310,32 +438,42 @@ impl<
     }
 
     /// ディスパッチャー注文を作成する
+    ///
+    /// `area_id` - ディスパッチャーの担当エリアID
+    ///
+    /// 注文とレッカー車の行をロックした上で割り当てを行うため、同じレッカー車や注文に対する
+    /// 同時の割り当ては1つだけが成功し、それ以外は `AppError::Conflict` を返す。
+    /// 注文またはレッカー車が担当エリア外の場合は `AppError::Forbidden` を返す
     pub async fn create_dispatcher_order(
         &self,
         order_id: i32,
         dispatcher_id: i32,
+        area_id: i32,
         tow_truck_id: i32,
         order_time: DateTime<Utc>,
     ) -> Result<(), AppError> {
-        if (self
-            .order_repository
-            .create_completed_order(order_id, tow_truck_id, order_time)
-            .await)
-            .is_err()
-        {
-            return Err(AppError::BadRequest);
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let order_area_id = self
+            .map_repository
+            .get_area_id_by_node_id(order.node_id)
+            .await?;
+        let tow_truck = self
+            .tow_truck_repository
+            .find_tow_truck_by_id(tow_truck_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if order_area_id != area_id || tow_truck.area_id != area_id {
+            return Err(AppError::Forbidden);
         }
 
         self.order_repository
-            .update_order_dispatched(order_id, dispatcher_id, tow_truck_id)
-            .await?;
-        self.tow_truck_repository
-            .update_status(tow_truck_id, "busy")
-            .await?;
-        Ok(())
+            .dispatch_order(order_id, dispatcher_id, tow_truck_id, order_time)
+            .await
     }
 
     /// 完了した注文を取得する
+    ///
+    /// キャンセルされた注文は含まない
     pub async fn get_completed_orders(&self) -> Result<Vec<CompletedOrderDto>, AppError> {
         let orders = self.order_repository.get_all_completed_orders().await?;
         let order_dtos = orders
@@ -344,4 +482,302 @@ impl<
             .collect();
         Ok(order_dtos)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::graph::Node;
+    use crate::models::tow_truck::TowTruck;
+    use crate::models::user::{Dispatcher, User};
+    use crate::repositories::in_memory::InMemoryRepository;
+
+    type TestOrderService = OrderService<
+        InMemoryRepository,
+        InMemoryRepository,
+        InMemoryRepository,
+        InMemoryRepository,
+    >;
+
+    const CLIENT: i32 = 1;
+    const AREA_DISPATCHER: i32 = 2;
+    const OTHER_AREA_DISPATCHER: i32 = 3;
+    const ASSIGNED_DRIVER: i32 = 4;
+    const OTHER_DRIVER: i32 = 5;
+
+    const ORDER_ID: i32 = 1;
+    const TOW_TRUCK_ID: i32 = 1;
+
+    /// エリア1の注文と、注文に割り当てられたレッカー車を用意する
+    fn order_service(status: OrderStatus) -> (TestOrderService, InMemoryRepository) {
+        let repository = InMemoryRepository::default();
+        {
+            let mut data = repository.data();
+            for (area_id, node_id) in [(1, 1), (2, 2)] {
+                data.nodes.push((
+                    area_id,
+                    Node {
+                        id: node_id,
+                        x: 0,
+                        y: 0,
+                    },
+                ));
+            }
+            for (id, role) in [
+                (CLIENT, Role::Client),
+                (AREA_DISPATCHER, Role::Dispatcher),
+                (OTHER_AREA_DISPATCHER, Role::Dispatcher),
+                (ASSIGNED_DRIVER, Role::Driver),
+                (OTHER_DRIVER, Role::Driver),
+            ] {
+                data.users.push(User {
+                    id,
+                    username: format!("user{}", id),
+                    password: String::new(),
+                    role: role.as_str().to_string(),
+                });
+            }
+            data.dispatchers.push(Dispatcher {
+                id: 1,
+                user_id: AREA_DISPATCHER,
+                area_id: 1,
+            });
+            data.dispatchers.push(Dispatcher {
+                id: 2,
+                user_id: OTHER_AREA_DISPATCHER,
+                area_id: 2,
+            });
+
+            let assigned = status != OrderStatus::Pending;
+            data.tow_trucks.push(TowTruck {
+                id: TOW_TRUCK_ID,
+                driver_id: ASSIGNED_DRIVER,
+                driver_username: None,
+                status: if assigned { "busy" } else { "available" }.to_string(),
+                area_id: 1,
+                node_id: 1,
+                location_updated_at: Utc::now(),
+            });
+            data.orders.push(Order {
+                id: ORDER_ID,
+                client_id: CLIENT,
+                dispatcher_id: assigned.then_some(1),
+                tow_truck_id: assigned.then_some(TOW_TRUCK_ID),
+                status: status.as_str().to_string(),
+                node_id: 1,
+                car_value: 100.0,
+                order_time: Utc::now(),
+                completed_time: None,
+            });
+        }
+
+        let service = OrderService::new(
+            repository.clone(),
+            repository.clone(),
+            repository.clone(),
+            repository.clone(),
+        );
+        (service, repository)
+    }
+
+    fn order_status(repository: &InMemoryRepository) -> String {
+        repository.data().orders[0].status.clone()
+    }
+
+    fn tow_truck_status(repository: &InMemoryRepository) -> String {
+        repository.data().tow_trucks[0].status.clone()
+    }
+
+    #[actix_rt::test]
+    async fn assigned_driver_and_area_dispatcher_can_complete_order() {
+        for user_id in [ASSIGNED_DRIVER, AREA_DISPATCHER] {
+            let (service, repository) = order_service(OrderStatus::Towing);
+            let user = repository.authenticated_user(user_id);
+
+            let dispatched_at = repository.data().orders[0].order_time;
+            repository.data().completed_orders.push(CompletedOrder {
+                id: 1,
+                order_id: ORDER_ID,
+                tow_truck_id: 1,
+                order_time: Some(dispatched_at),
+                completed_time: dispatched_at,
+                car_value: 100.0,
+            });
+
+            service.complete_order(ORDER_ID, &user).await.unwrap();
+
+            assert_eq!(order_status(&repository), "completed");
+            assert_eq!(tow_truck_status(&repository), "available");
+            let data = repository.data();
+            let completed_time = data.orders[0].completed_time.unwrap();
+            assert_eq!(data.completed_orders[0].completed_time, completed_time);
+            assert!(completed_time > dispatched_at);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn other_users_cannot_complete_order() {
+        for user_id in [CLIENT, OTHER_AREA_DISPATCHER, OTHER_DRIVER] {
+            let (service, repository) = order_service(OrderStatus::Towing);
+            let user = repository.authenticated_user(user_id);
+
+            let result = service.complete_order(ORDER_ID, &user).await;
+
+            assert!(
+                matches!(result, Err(AppError::Forbidden)),
+                "user {}",
+                user_id
+            );
+            assert_eq!(order_status(&repository), "towing");
+            assert_eq!(tow_truck_status(&repository), "busy");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn client_can_cancel_own_order_only_while_pending() {
+        let (service, repository) = order_service(OrderStatus::Pending);
+        let client = repository.authenticated_user(CLIENT);
+        service
+            .cancel_order(ORDER_ID, "client_request", &client)
+            .await
+            .unwrap();
+        assert_eq!(order_status(&repository), "cancelled");
+
+        let (service, repository) = order_service(OrderStatus::Dispatched);
+        let client = repository.authenticated_user(CLIENT);
+        let result = service
+            .cancel_order(ORDER_ID, "client_request", &client)
+            .await;
+        assert!(matches!(result, Err(AppError::Forbidden)));
+        assert_eq!(order_status(&repository), "dispatched");
+    }
+
+    #[actix_rt::test]
+    async fn area_dispatcher_can_cancel_and_release_tow_truck() {
+        for status in [
+            OrderStatus::Pending,
+            OrderStatus::Dispatched,
+            OrderStatus::EnRoute,
+            OrderStatus::Arrived,
+            OrderStatus::Towing,
+        ] {
+            let (service, repository) = order_service(status);
+            let dispatcher = repository.authenticated_user(AREA_DISPATCHER);
+
+            service
+                .cancel_order(ORDER_ID, "no_tow_truck_available", &dispatcher)
+                .await
+                .unwrap();
+
+            assert_eq!(order_status(&repository), "cancelled", "{}", status);
+            assert_eq!(tow_truck_status(&repository), "available", "{}", status);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn other_area_dispatcher_and_drivers_cannot_cancel() {
+        for user_id in [OTHER_AREA_DISPATCHER, ASSIGNED_DRIVER, OTHER_DRIVER] {
+            let (service, repository) = order_service(OrderStatus::Dispatched);
+            let user = repository.authenticated_user(user_id);
+
+            let result = service.cancel_order(ORDER_ID, "other", &user).await;
+
+            assert!(
+                matches!(result, Err(AppError::Forbidden)),
+                "user {}",
+                user_id
+            );
+            assert_eq!(order_status(&repository), "dispatched");
+            assert_eq!(tow_truck_status(&repository), "busy");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn completed_order_cannot_be_cancelled() {
+        let (service, repository) = order_service(OrderStatus::Completed);
+        let dispatcher = repository.authenticated_user(AREA_DISPATCHER);
+
+        let result = service.cancel_order(ORDER_ID, "other", &dispatcher).await;
+
+        assert!(matches!(
+            result,
+            Err(AppError::InvalidOrderStatusTransition {
+                from: OrderStatus::Completed,
+                to: OrderStatus::Cancelled,
+            })
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn rejects_unknown_cancel_reason() {
+        let (service, repository) = order_service(OrderStatus::Pending);
+        let client = repository.authenticated_user(CLIENT);
+
+        let result = service.cancel_order(ORDER_ID, "bored", &client).await;
+
+        assert!(matches!(result, Err(AppError::InvalidCancelReason(_))));
+        assert_eq!(order_status(&repository), "pending");
+    }
+
+    #[actix_rt::test]
+    async fn assigned_driver_and_area_dispatcher_can_update_status() {
+        for user_id in [ASSIGNED_DRIVER, AREA_DISPATCHER] {
+            let (service, repository) = order_service(OrderStatus::Dispatched);
+            let user = repository.authenticated_user(user_id);
+
+            service
+                .update_order_status(ORDER_ID, "en_route", &user)
+                .await
+                .unwrap();
+
+            assert_eq!(order_status(&repository), "en_route");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn other_users_cannot_update_status() {
+        for user_id in [CLIENT, OTHER_AREA_DISPATCHER, OTHER_DRIVER] {
+            let (service, repository) = order_service(OrderStatus::Dispatched);
+            let user = repository.authenticated_user(user_id);
+
+            let result = service
+                .update_order_status(ORDER_ID, "en_route", &user)
+                .await;
+
+            assert!(
+                matches!(result, Err(AppError::Forbidden)),
+                "user {}",
+                user_id
+            );
+            assert_eq!(order_status(&repository), "dispatched");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn client_driver_and_area_dispatcher_can_get_order() {
+        for user_id in [CLIENT, ASSIGNED_DRIVER, AREA_DISPATCHER] {
+            let (service, repository) = order_service(OrderStatus::Dispatched);
+            let user = repository.authenticated_user(user_id);
+
+            let order = service.get_order_by_id(ORDER_ID, &user).await.unwrap();
+
+            assert_eq!(order.id, ORDER_ID, "user {}", user_id);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn other_users_cannot_get_order() {
+        for user_id in [OTHER_AREA_DISPATCHER, OTHER_DRIVER] {
+            let (service, repository) = order_service(OrderStatus::Dispatched);
+            let user = repository.authenticated_user(user_id);
+
+            let result = service.get_order_by_id(ORDER_ID, &user).await;
+
+            assert!(
+                matches!(result, Err(AppError::Forbidden)),
+                "user {}",
+                user_id
+            );
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::errors::{AppError, FieldError};
+
+/// ページ番号と1ページあたりの件数を検証する
+///
+/// `page` - ページ番号（0以上）
+/// `page_size` - 1ページあたりの件数（1以上、または全件を取得する `-1`）
+///
+/// 満たしていない場合や、取得を開始する位置が大きすぎて表せない場合は `AppError::ValidationFailed` を返す
+pub fn validate_pagination(page: i32, page_size: i32) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+    if page < 0 {
+        errors.push(FieldError::new("page", "page must be at least 0"));
+    }
+    if page_size < 1 && page_size != -1 {
+        errors.push(FieldError::new(
+            "page_size",
+            "page_size must be at least 1, or -1 for all",
+        ));
+    }
+    if errors.is_empty() && page_size != -1 && page.checked_mul(page_size).is_none() {
+        errors.push(FieldError::new("page", "page is too large for page_size"));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::ValidationFailed(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_fields(page: i32, page_size: i32) -> Vec<String> {
+        match validate_pagination(page, page_size) {
+            Ok(()) => Vec::new(),
+            Err(err) => err
+                .field_errors()
+                .into_iter()
+                .map(|error| error.field)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_pagination() {
+        for (page, page_size) in [(0, 1), (3, 10), (0, -1), (i32::MAX, -1)] {
+            assert!(invalid_fields(page, page_size).is_empty());
+        }
+    }
+
+    #[test]
+    fn rejects_negative_page_and_page_size() {
+        assert_eq!(invalid_fields(-1, 10), ["page"]);
+        assert_eq!(invalid_fields(0, 0), ["page_size"]);
+        assert_eq!(invalid_fields(0, -5), ["page_size"]);
+        assert_eq!(invalid_fields(-1, -5), ["page", "page_size"]);
+    }
+
+    #[test]
+    fn rejects_offset_overflow() {
+        assert_eq!(invalid_fields(i32::MAX, 2), ["page"]);
+    }
+}
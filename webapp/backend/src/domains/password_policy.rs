@@ -0,0 +1,113 @@
+use crate::errors::AppError;
+
+/// パスワードの強度の設定を表す構造体
+///
+/// `min_length` - 最小文字数
+/// `max_length` - 最大文字数（ハッシュ化の負荷を抑えるため）
+/// `require_letter` - 英字を1文字以上含める必要があるか
+/// `require_digit` - 数字を1文字以上含める必要があるか
+/// `require_symbol` - 英数字以外の文字を1文字以上含める必要があるか
+#[derive(Clone, Copy, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_letter: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+}
+
+impl PasswordPolicy {
+    /// パスワードが設定を満たしているかを検証する
+    ///
+    /// `field` - エラーに含めるリクエストの項目名
+    /// `password` - 検証するパスワード
+    ///
+    /// 満たしていない場合は理由を含む `AppError::InvalidPassword` を返す
+    pub fn validate(&self, field: &'static str, password: &str) -> Result<(), AppError> {
+        let invalid = |reason: String| Err(AppError::InvalidPassword { field, reason });
+
+        let length = password.chars().count();
+        if length < self.min_length {
+            return invalid(format!("must be at least {} characters", self.min_length));
+        }
+        if length > self.max_length {
+            return invalid(format!("must be at most {} characters", self.max_length));
+        }
+        if self.require_letter && !password.chars().any(|c| c.is_alphabetic()) {
+            return invalid("must contain a letter".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_numeric()) {
+            return invalid("must contain a digit".to_string());
+        }
+        if self.require_symbol && password.chars().all(|c| c.is_alphanumeric()) {
+            return invalid("must contain a symbol".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 16,
+            require_letter: true,
+            require_digit: true,
+            require_symbol: true,
+        }
+    }
+
+    fn reason(result: Result<(), AppError>) -> Option<String> {
+        match result {
+            Ok(()) => None,
+            Err(AppError::InvalidPassword { reason, .. }) => Some(reason),
+            Err(err) => panic!("unexpected error: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn accepts_password_satisfying_policy() {
+        assert_eq!(reason(policy().validate("password", "passw0rd!")), None);
+    }
+
+    #[test]
+    fn rejects_password_outside_length_range() {
+        assert_eq!(
+            reason(policy().validate("password", "pa0!")),
+            Some("must be at least 8 characters".to_string())
+        );
+        assert_eq!(
+            reason(policy().validate("password", "passw0rd!passw0rd!")),
+            Some("must be at most 16 characters".to_string())
+        );
+    }
+
+    #[test]
+    fn counts_characters_instead_of_bytes() {
+        assert_eq!(
+            reason(policy().validate("password", "パスワード1!")),
+            Some("must be at least 8 characters".to_string())
+        );
+        assert_eq!(reason(policy().validate("password", "パスワードです12!")), None);
+    }
+
+    #[test]
+    fn rejects_password_missing_required_characters() {
+        assert_eq!(
+            reason(policy().validate("password", "12345678!")),
+            Some("must contain a letter".to_string())
+        );
+        assert_eq!(
+            reason(policy().validate("password", "password!")),
+            Some("must contain a digit".to_string())
+        );
+        assert_eq!(
+            reason(policy().validate("password", "passw0rd")),
+            Some("must contain a symbol".to_string())
+        );
+    }
+}
@@ -1,9 +1,19 @@
-use super::dto::tow_truck::TowTruckDto;
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+
+use super::dto::tow_truck::{
+    LocationHistoryDto, NearestTowTruckDto, TowTruckDto, TowTruckRouteDto,
+};
 use super::map_service::MapRepository;
 use super::order_service::OrderRepository;
-use crate::errors::AppError;
-use crate::models::graph::Graph;
-use crate::models::tow_truck::TowTruck;
+use super::pagination::validate_pagination;
+use crate::errors::{AppError, FieldError};
+use crate::models::graph::{Graph, GraphCache, RouteAlgorithm};
+use crate::models::tow_truck::{LocationPlausibilityPolicy, LocationRecord, TowTruck};
+use crate::models::user::{AuthenticatedUser, Role};
 
 /// レッカー車リポジトリのトレイト
 pub trait TowTruckRepository {
@@ -16,16 +26,31 @@ pub trait TowTruckRepository {
         area_id: Option<i32>,
     ) -> Result<Vec<TowTruck>, AppError>;
     
-    /// レッカー車の位置を更新する
-    async fn update_location(&self, truck_id: i32, node_id: i32) -> Result<(), AppError>;
-    
-    /// レッカー車のステータスを更新する
-    async fn update_status(&self, truck_id: i32, status: &str) -> Result<(), AppError>;
+    /// レッカー車の位置を更新し、履歴に記録する
+    async fn update_location(
+        &self,
+        truck_id: i32,
+        node_id: i32,
+        timestamp: DateTime<Utc>,
+        implausible: bool,
+    ) -> Result<(), AppError>;
     
     /// IDに基づいてレッカー車を検索する
     async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError>;
+
+    /// レッカー車の位置の履歴を古い順に最大 `limit` 件取得する
+    async fn find_location_history(
+        &self,
+        tow_truck_id: i32,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i32,
+    ) -> Result<Vec<LocationRecord>, AppError>;
 }
 
+/// 一度に取得するレッカー車の位置の履歴の最大件数
+const LOCATION_HISTORY_LIMIT: usize = 1000;
+
 /// レッカー車サービスの構造体
 #[derive(Debug)]
 pub struct TowTruckService<
@@ -36,6 +61,9 @@ pub struct TowTruckService<
     tow_truck_repository: T,
     order_repository: U,
     map_repository: V,
+    graph_cache: Arc<GraphCache>,
+    tow_truck_speed: f64,
+    location_plausibility: Option<LocationPlausibilityPolicy>,
 }
 
 impl<
@@ -45,21 +73,73 @@ impl<
     > TowTruckService<T, U, V>
 {
     /// 新しいレッカー車サービスを作成する
-    pub fn new(tow_truck_repository: T, order_repository: U, map_repository: V) -> Self {
+    /// 
+    /// `tow_truck_speed` - レッカー車が1秒あたりに進むグラフ上の距離（到着予定時刻の計算に用いる）
+    /// `location_plausibility` - 位置の更新が現実的な移動かどうかの判定（`None` の場合は判定しない）
+    pub fn new(
+        tow_truck_repository: T,
+        order_repository: U,
+        map_repository: V,
+        graph_cache: Arc<GraphCache>,
+        tow_truck_speed: f64,
+        location_plausibility: Option<LocationPlausibilityPolicy>,
+    ) -> Self {
         TowTruckService {
             tow_truck_repository,
             order_repository,
             map_repository,
+            graph_cache,
+            tow_truck_speed,
+            location_plausibility,
         }
     }
 
+    /// エリアのグラフを取得する
+    ///
+    /// キャッシュに存在しない場合のみデータベースからノードとエッジを読み込んでグラフを構築し、
+    /// 以降のリクエストではキャッシュ上のグラフを再利用する
+    async fn get_area_graph(&self, area_id: i32) -> Result<Arc<RwLock<Graph>>, AppError> {
+        if let Some(graph) = self.graph_cache.get(area_id) {
+            return Ok(graph);
+        }
+
+        // 読み込み中のエッジの更新を検知するため、読み込みを始める前の世代を取得しておく
+        let generation = self.graph_cache.generation(area_id);
+        let nodes = self.map_repository.get_all_nodes(Some(area_id)).await?;
+        let edges = self.map_repository.get_all_edges(Some(area_id)).await?;
+
+        let mut graph = Graph::new();
+        for node in nodes {
+            graph.add_node(node);
+        }
+        for edge in edges {
+            graph.add_edge(edge);
+        }
+
+        Ok(self.graph_cache.insert(area_id, generation, graph))
+    }
+
     /// IDに基づいてレッカー車を取得する
-    pub async fn get_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruckDto>, AppError> {
+    ///
+    /// `user` - レッカー車を取得する認証済みのユーザー
+    ///
+    /// ディスパッチャーは担当エリアのレッカー車、ドライバーは自身が運転するレッカー車のみ取得でき、
+    /// それ以外の場合は `AppError::Forbidden` を返す
+    pub async fn get_tow_truck_by_id(
+        &self,
+        id: i32,
+        user: &AuthenticatedUser,
+    ) -> Result<Option<TowTruckDto>, AppError> {
         let tow_truck = self.tow_truck_repository.find_tow_truck_by_id(id).await?;
-        Ok(tow_truck.map(TowTruckDto::from_entity))
+        match tow_truck {
+            Some(tow_truck) if !can_access_tow_truck(user, &tow_truck) => Err(AppError::Forbidden),
+            tow_truck => Ok(tow_truck.map(TowTruckDto::from_entity)),
+        }
     }
 
     /// ページネーションされたレッカー車リストを取得する
+    ///
+    /// `page` または `page_size` が不正な場合は `AppError::ValidationFailed` を返す
     pub async fn get_all_tow_trucks(
         &self,
         page: i32,
@@ -67,6 +147,7 @@ impl<
         status: Option<String>,
         area: Option<i32>,
     ) -> Result<Vec<TowTruckDto>, AppError> {
+        validate_pagination(page, page_size)?;
         let tow_trucks = self
             .tow_truck_repository
             .get_paginated_tow_trucks(page, page_size, status, area)
@@ -80,72 +161,508 @@ impl<
     }
 
     /// レッカー車の位置を更新する
-    pub async fn update_location(&self, truck_id: i32, node_id: i32) -> Result<(), AppError> {
+    ///
+    /// `truck_id` - レッカー車ID
+    /// `driver_id` - 位置を更新するドライバーのユーザーID
+    /// `node_id` - 現在地のノードID
+    ///
+    /// レッカー車が存在しない場合は `AppError::NotFound`、
+    /// ドライバーが運転するレッカー車でない場合は `AppError::Forbidden`、
+    /// ノードがレッカー車のエリアに存在しない場合は `AppError::ValidationFailed` を返す。
+    /// 前回の位置から経過時間内にグラフ上で到達できない移動は拒否せず、履歴に `implausible` として記録する
+    pub async fn update_location(
+        &self,
+        truck_id: i32,
+        driver_id: i32,
+        node_id: i32,
+    ) -> Result<(), AppError> {
+        let tow_truck = self
+            .tow_truck_repository
+            .find_tow_truck_by_id(truck_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if tow_truck.driver_id != driver_id {
+            return Err(AppError::Forbidden);
+        }
+
+        let now = Utc::now();
+        let graph = self.get_area_graph(tow_truck.area_id).await?;
+        let implausible = {
+            let graph = graph.read().unwrap_or_else(PoisonError::into_inner);
+            if !graph.nodes.contains_key(&node_id) {
+                return Err(AppError::ValidationFailed(vec![FieldError::new(
+                    "node_id",
+                    format!("node {} is not in area {}", node_id, tow_truck.area_id),
+                )]));
+            }
+
+            match self.location_plausibility {
+                Some(policy) => {
+                    let distance = match graph.shortest_path(tow_truck.node_id, node_id) {
+                        i32::MAX => None,
+                        distance => Some(distance),
+                    };
+                    !policy.is_plausible(distance, now - tow_truck.location_updated_at)
+                }
+                None => false,
+            }
+        };
+        if implausible {
+            warn!(
+                "Implausible move of tow truck {} from node {} to node {} since {}",
+                truck_id, tow_truck.node_id, node_id, tow_truck.location_updated_at
+            );
+        }
+
         self.tow_truck_repository
-            .update_location(truck_id, node_id)
+            .update_location(truck_id, node_id, now, implausible)
             .await?;
 
         Ok(())
     }
 
-    /// 最寄りの利用可能なレッカー車を取得する
+    /// レッカー車の位置の履歴を、グラフ上で移動したコストとともに取得する
+    ///
+    /// `tow_truck_id` - レッカー車ID
+    /// `user` - 履歴を取得する認証済みのユーザー
+    /// `from` - 取得する期間の開始時刻
+    /// `to` - 取得する期間の終了時刻
+    ///
+    /// 古い方から最大 `LOCATION_HISTORY_LIMIT` 件を返し、それより多い場合は `truncated` を立てる。
+    /// 連続する2地点間のコストはレッカー車のエリアのグラフ上の最短距離とし、
+    /// 同じノードの組の探索は一度だけ行う。
+    /// ディスパッチャーは担当エリアのレッカー車、ドライバーは自身が運転するレッカー車のみ取得でき、
+    /// それ以外の場合は `AppError::Forbidden` を返す
+    pub async fn get_location_history(
+        &self,
+        tow_truck_id: i32,
+        user: &AuthenticatedUser,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<LocationHistoryDto, AppError> {
+        if matches!((from, to), (Some(from), Some(to)) if from > to) {
+            return Err(AppError::ValidationFailed(vec![FieldError::new(
+                "from",
+                "must not be after to",
+            )]));
+        }
+
+        let tow_truck = self
+            .tow_truck_repository
+            .find_tow_truck_by_id(tow_truck_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if !can_access_tow_truck(user, &tow_truck) {
+            return Err(AppError::Forbidden);
+        }
+
+        // 上限を超えているかどうかを判定するために1件多く取得する
+        let mut locations = self
+            .tow_truck_repository
+            .find_location_history(tow_truck_id, from, to, LOCATION_HISTORY_LIMIT as i32 + 1)
+            .await?;
+        let truncated = locations.len() > LOCATION_HISTORY_LIMIT;
+        locations.truncate(LOCATION_HISTORY_LIMIT);
+
+        let graph = self.get_area_graph(tow_truck.area_id).await?;
+        let graph = graph.read().unwrap_or_else(PoisonError::into_inner);
+
+        let mut costs: HashMap<(i32, i32), Option<i32>> = HashMap::new();
+        Ok(LocationHistoryDto::from_records(
+            tow_truck_id,
+            locations,
+            truncated,
+            |from_node_id, to_node_id| {
+                if from_node_id == to_node_id {
+                    return Some(0);
+                }
+                *costs.entry((from_node_id, to_node_id)).or_insert_with(|| {
+                    match graph.shortest_path(from_node_id, to_node_id) {
+                        i32::MAX => None,
+                        cost => Some(cost),
+                    }
+                })
+            },
+        ))
+    }
+
+    /// レッカー車の現在地から注文地点までの最短経路を取得する
+    ///
+    /// `tow_truck_id` - レッカー車ID
+    /// `order_id` - 注文ID
+    /// `user` - 経路を取得する認証済みのユーザー
+    /// `algorithm` - 経路の探索方法
+    ///
+    /// ディスパッチャーは担当エリアのレッカー車、ドライバーは自身が運転するレッカー車の経路のみ取得できる。
+    /// それ以外の場合や、注文がレッカー車と異なるエリアの場合は `AppError::Forbidden` を返す。
+    /// レッカー車が存在しない場合や、注文地点まで到達できない場合は `AppError::NotFound` を返す
+    pub async fn get_route_to_order(
+        &self,
+        tow_truck_id: i32,
+        order_id: i32,
+        user: &AuthenticatedUser,
+        algorithm: RouteAlgorithm,
+    ) -> Result<TowTruckRouteDto, AppError> {
+        let tow_truck = self
+            .tow_truck_repository
+            .find_tow_truck_by_id(tow_truck_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if !can_access_tow_truck(user, &tow_truck) {
+            return Err(AppError::Forbidden);
+        }
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let order_area_id = self
+            .map_repository
+            .get_area_id_by_node_id(order.node_id)
+            .await?;
+        if order_area_id != tow_truck.area_id {
+            return Err(AppError::Forbidden);
+        }
+
+        let graph = self.get_area_graph(tow_truck.area_id).await?;
+        let graph = graph.read().unwrap_or_else(PoisonError::into_inner);
+        let route = match algorithm {
+            RouteAlgorithm::Dijkstra => graph.shortest_route(tow_truck.node_id, order.node_id),
+            RouteAlgorithm::AStar => graph.shortest_route_astar(tow_truck.node_id, order.node_id),
+        }
+        .ok_or(AppError::NotFound)?;
+
+        Ok(TowTruckRouteDto::from_route(tow_truck.id, order.id, route))
+    }
+
+    /// 注文地点から近い順に、利用可能なレッカー車を最大 `k` 台取得する
+    /// 
+    /// `order_id` - 注文ID
+    /// `user` - レッカー車を検索する認証済みのディスパッチャー
+    /// `k` - 取得するレッカー車の最大台数
+    /// `max_distance` - 注文地点までの距離の上限（`None` の場合は到達可能な全てのレッカー車が対象）
+    /// 
+    /// 注文地点まで到達できないレッカー車は結果に含まれない。
+    /// 注文がディスパッチャーの担当エリア外の場合は `AppError::Forbidden` を返す
     /// 
     /// ボトルネックになりうる箇所: グラフ計算とソート処理
-    /// - グラフの構築と最短経路計算は計算コストが高い可能性があります
+    /// - グラフはエリアごとにキャッシュされるため、構築は初回のみ行われます
+    /// - 最短距離は注文のノードを始点とした1回の探索で全てのレッカー車分をまとめて計算します
     /// - レッカー車のソートも、レッカー車の数が多い場合は処理時間がかかる可能性があります
     pub async fn get_nearest_available_tow_trucks(
         &self,
         order_id: i32,
-    ) -> Result<Option<TowTruckDto>, AppError> {
+        user: &AuthenticatedUser,
+        k: usize,
+        max_distance: Option<i32>,
+    ) -> Result<Vec<NearestTowTruckDto>, AppError> {
         let order = self.order_repository.find_order_by_id(order_id).await?;
         let area_id = self
             .map_repository
             .get_area_id_by_node_id(order.node_id)
             .await?;
+        if user.area_id() != Some(area_id) {
+            return Err(AppError::Forbidden);
+        }
         let tow_trucks = self
             .tow_truck_repository
             .get_paginated_tow_trucks(0, -1, Some("available".to_string()), Some(area_id))
             .await?;
 
-        let nodes = self.map_repository.get_all_nodes(Some(area_id)).await?;
-        let edges = self.map_repository.get_all_edges(Some(area_id)).await?;
+        let graph = self.get_area_graph(area_id).await?;
+        let graph = graph.read().unwrap_or_else(PoisonError::into_inner);
 
-        let mut graph = Graph::new();
-        for node in nodes {
-            graph.add_node(node);
+        // 注文のノードから1回の探索で全てのレッカー車までの距離を求める
+        let truck_node_ids: Vec<i32> = tow_trucks.iter().map(|truck| truck.node_id).collect();
+        let distances = graph.shortest_paths(order.node_id, &truck_node_ids);
+
+        let mut tow_trucks_with_distance: Vec<_> = tow_trucks
+            .into_iter()
+            .filter_map(|truck| {
+                let distance = *distances.get(&truck.node_id)?;
+                match max_distance {
+                    Some(max_distance) if distance > max_distance => None,
+                    _ => Some((distance, truck)),
+                }
+            })
+            .collect();
+        tow_trucks_with_distance.sort_by_key(|(distance, _)| *distance);
+
+        let now = Utc::now();
+        let nearest_tow_truck_dtos = tow_trucks_with_distance
+            .into_iter()
+            .take(k)
+            .map(|(distance, truck)| {
+                NearestTowTruckDto::from_entity(truck, distance, self.tow_truck_speed, now)
+            })
+            .collect();
+
+        Ok(nearest_tow_truck_dtos)
+    }
+}
+
+/// ユーザーがレッカー車の担当エリアのディスパッチャー、またはレッカー車のドライバーかどうかを返す
+fn can_access_tow_truck(user: &AuthenticatedUser, tow_truck: &TowTruck) -> bool {
+    match user.user.role.parse::<Role>() {
+        Ok(Role::Dispatcher) => user.area_id() == Some(tow_truck.area_id),
+        Ok(Role::Driver) => tow_truck.driver_id == user.user.id,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::graph::{Edge, Node};
+    use crate::models::order::Order;
+    use crate::models::user::{Dispatcher, User};
+    use crate::repositories::in_memory::InMemoryRepository;
+
+    type TestTowTruckService =
+        TowTruckService<InMemoryRepository, InMemoryRepository, InMemoryRepository>;
+
+    const AREA_DISPATCHER: i32 = 1;
+    const OTHER_AREA_DISPATCHER: i32 = 2;
+    const DRIVER: i32 = 3;
+    const OTHER_DRIVER: i32 = 4;
+
+    const TOW_TRUCK_ID: i32 = 1;
+    const AREA_ORDER_ID: i32 = 1;
+    const OTHER_AREA_ORDER_ID: i32 = 2;
+
+    /// エリア1のノード1とノード2を結ぶ道路、ノード2にいるレッカー車、
+    /// ノード1（エリア1）とノード3（エリア2）の注文を用意する
+    fn tow_truck_service() -> (TestTowTruckService, InMemoryRepository) {
+        let repository = InMemoryRepository::default();
+        {
+            let mut data = repository.data();
+            for (area_id, node_id) in [(1, 1), (1, 2), (2, 3)] {
+                data.nodes.push((
+                    area_id,
+                    Node {
+                        id: node_id,
+                        x: 0,
+                        y: 0,
+                    },
+                ));
+            }
+            data.edges.push(Edge {
+                node_a_id: 1,
+                node_b_id: 2,
+                weight: 5,
+            });
+            for (id, role) in [
+                (AREA_DISPATCHER, Role::Dispatcher),
+                (OTHER_AREA_DISPATCHER, Role::Dispatcher),
+                (DRIVER, Role::Driver),
+                (OTHER_DRIVER, Role::Driver),
+            ] {
+                data.users.push(User {
+                    id,
+                    username: format!("user{}", id),
+                    password: String::new(),
+                    role: role.as_str().to_string(),
+                });
+            }
+            data.dispatchers.push(Dispatcher {
+                id: 1,
+                user_id: AREA_DISPATCHER,
+                area_id: 1,
+            });
+            data.dispatchers.push(Dispatcher {
+                id: 2,
+                user_id: OTHER_AREA_DISPATCHER,
+                area_id: 2,
+            });
+            data.tow_trucks.push(TowTruck {
+                id: TOW_TRUCK_ID,
+                driver_id: DRIVER,
+                driver_username: None,
+                status: "available".to_string(),
+                area_id: 1,
+                node_id: 2,
+                location_updated_at: Utc::now(),
+            });
+            for (id, node_id) in [(AREA_ORDER_ID, 1), (OTHER_AREA_ORDER_ID, 3)] {
+                data.orders.push(Order {
+                    id,
+                    client_id: 5,
+                    dispatcher_id: None,
+                    tow_truck_id: None,
+                    status: "pending".to_string(),
+                    node_id,
+                    car_value: 100.0,
+                    order_time: Utc::now(),
+                    completed_time: None,
+                });
+            }
         }
-        for edge in edges {
-            graph.add_edge(edge);
+
+        let service = TowTruckService::new(
+            repository.clone(),
+            repository.clone(),
+            repository.clone(),
+            Arc::new(GraphCache::new(None)),
+            1.0,
+            None,
+        );
+        (service, repository)
+    }
+
+    #[actix_rt::test]
+    async fn area_dispatcher_and_driver_can_get_tow_truck() {
+        let (service, repository) = tow_truck_service();
+        for user_id in [AREA_DISPATCHER, DRIVER] {
+            let user = repository.authenticated_user(user_id);
+
+            let tow_truck = service.get_tow_truck_by_id(TOW_TRUCK_ID, &user).await;
+
+            assert!(
+                matches!(tow_truck, Ok(Some(ref tow_truck)) if tow_truck.id == TOW_TRUCK_ID),
+                "user {}",
+                user_id
+            );
         }
+    }
 
-        let sorted_tow_trucks_by_distance = {
-            let mut tow_trucks_with_distance: Vec<_> = tow_trucks
-                .into_iter()
-                .map(|truck| {
-                    let distance = calculate_distance(&graph, truck.node_id, order.node_id);
-                    (distance, truck)
-                })
-                .collect();
+    #[actix_rt::test]
+    async fn other_users_cannot_get_tow_truck() {
+        let (service, repository) = tow_truck_service();
+        for user_id in [OTHER_AREA_DISPATCHER, OTHER_DRIVER] {
+            let user = repository.authenticated_user(user_id);
 
-            tow_trucks_with_distance.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-            tow_trucks_with_distance
-        };
+            let result = service.get_tow_truck_by_id(TOW_TRUCK_ID, &user).await;
 
-        if sorted_tow_trucks_by_distance.is_empty() || sorted_tow_trucks_by_distance[0].0 > 10000000
-        {
-            return Ok(None);
+            assert!(
+                matches!(result, Err(AppError::Forbidden)),
+                "user {}",
+                user_id
+            );
         }
+    }
 
-        let sorted_tow_truck_dtos: Vec<TowTruckDto> = sorted_tow_trucks_by_distance
-            .into_iter()
-            .map(|(_, truck)| TowTruckDto::from_entity(truck))
+    #[actix_rt::test]
+    async fn driver_can_get_route_to_order_in_same_area() {
+        let (service, repository) = tow_truck_service();
+        let user = repository.authenticated_user(DRIVER);
+
+        let route = service
+            .get_route_to_order(TOW_TRUCK_ID, AREA_ORDER_ID, &user, RouteAlgorithm::Dijkstra)
+            .await
+            .unwrap();
+        let astar_route = service
+            .get_route_to_order(TOW_TRUCK_ID, AREA_ORDER_ID, &user, RouteAlgorithm::AStar)
+            .await
+            .unwrap();
+
+        assert_eq!(route.node_ids, vec![2, 1]);
+        assert_eq!(route.distance, 5);
+        assert_eq!(astar_route.node_ids, route.node_ids);
+    }
+
+    #[actix_rt::test]
+    async fn route_is_forbidden_for_other_driver_or_order_in_other_area() {
+        let (service, repository) = tow_truck_service();
+        let driver = repository.authenticated_user(DRIVER);
+        let other_driver = repository.authenticated_user(OTHER_DRIVER);
+
+        let other_area_order = service
+            .get_route_to_order(
+                TOW_TRUCK_ID,
+                OTHER_AREA_ORDER_ID,
+                &driver,
+                RouteAlgorithm::Dijkstra,
+            )
+            .await;
+        let other_driver_route = service
+            .get_route_to_order(
+                TOW_TRUCK_ID,
+                AREA_ORDER_ID,
+                &other_driver,
+                RouteAlgorithm::Dijkstra,
+            )
+            .await;
+
+        assert!(matches!(other_area_order, Err(AppError::Forbidden)));
+        assert!(matches!(other_driver_route, Err(AppError::Forbidden)));
+    }
+
+    #[actix_rt::test]
+    async fn nearest_tow_trucks_are_limited_to_dispatcher_area() {
+        let (service, repository) = tow_truck_service();
+        let dispatcher = repository.authenticated_user(AREA_DISPATCHER);
+        let other_area_dispatcher = repository.authenticated_user(OTHER_AREA_DISPATCHER);
+
+        let nearest = service
+            .get_nearest_available_tow_trucks(AREA_ORDER_ID, &dispatcher, 1, None)
+            .await
+            .unwrap();
+        let other_area_order = service
+            .get_nearest_available_tow_trucks(OTHER_AREA_ORDER_ID, &dispatcher, 1, None)
+            .await;
+        let other_area_dispatcher_result = service
+            .get_nearest_available_tow_trucks(AREA_ORDER_ID, &other_area_dispatcher, 1, None)
+            .await;
+
+        assert_eq!(nearest.len(), 1);
+        assert_eq!(nearest[0].tow_truck.id, TOW_TRUCK_ID);
+        assert!(matches!(other_area_order, Err(AppError::Forbidden)));
+        assert!(matches!(
+            other_area_dispatcher_result,
+            Err(AppError::Forbidden)
+        ));
+    }
+
+    /// レッカー車がノードを `node_ids` の順に訪れた履歴を追加する
+    fn push_locations(repository: &InMemoryRepository, node_ids: impl IntoIterator<Item = i32>) {
+        let mut data = repository.data();
+        for node_id in node_ids {
+            data.locations.push((
+                TOW_TRUCK_ID,
+                LocationRecord {
+                    node_id,
+                    x: 0,
+                    y: 0,
+                    timestamp: None,
+                    implausible: false,
+                },
+            ));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn location_history_sums_costs_between_visits() {
+        let (service, repository) = tow_truck_service();
+        push_locations(&repository, [2, 1, 1, 2]);
+        let driver = repository.authenticated_user(DRIVER);
+
+        let history = service
+            .get_location_history(TOW_TRUCK_ID, &driver, None, None)
+            .await
+            .unwrap();
+
+        let costs: Vec<_> = history
+            .visits
+            .iter()
+            .map(|visit| visit.cost_from_previous)
             .collect();
+        assert_eq!(costs, vec![None, Some(5), Some(0), Some(5)]);
+        assert_eq!(history.total_cost, 10);
+        assert!(!history.truncated);
+    }
 
-        Ok(sorted_tow_truck_dtos.first().cloned())
+    #[actix_rt::test]
+    async fn location_history_is_truncated_at_limit() {
+        let (service, repository) = tow_truck_service();
+        push_locations(
+            &repository,
+            (0..=LOCATION_HISTORY_LIMIT).map(|i| 1 + i as i32 % 2),
+        );
+        let dispatcher = repository.authenticated_user(AREA_DISPATCHER);
+
+        let history = service
+            .get_location_history(TOW_TRUCK_ID, &dispatcher, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(history.visits.len(), LOCATION_HISTORY_LIMIT);
+        assert_eq!(history.total_cost, 5 * (LOCATION_HISTORY_LIMIT as i64 - 1));
+        assert!(history.truncated);
     }
 }
-
-/// グラフ上の2つのノード間の最短距離を計算する
-fn calculate_distance(graph: &Graph, node_id_1: i32, node_id_2: i32) -> i32 {
-    graph.shortest_path(node_id_1, node_id_2)
-}
\ No newline at end of file
@@ -1,7 +1,12 @@
+use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
+use log::error;
 use serde::Serialize;
+use sqlx::mysql::MySqlDatabaseError;
 use thiserror::Error;
 
+use crate::models::order::OrderStatus;
+
 /// アプリケーションエラーの列挙型
 #[derive(Debug, Error)]
 pub enum AppError {
@@ -9,39 +14,259 @@ pub enum AppError {
     BadRequest,
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Forbidden")]
+    Forbidden,
     #[error("Not Found")]
     NotFound,
     #[error("Conflict")]
     Conflict,
     #[error("Internal Server Error")]
     InternalServerError,
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Validation failed")]
+    ValidationFailed(Vec<FieldError>),
+    #[error("Invalid order status: {0}")]
+    InvalidOrderStatus(String),
+    #[error("Invalid order status transition from {from} to {to}")]
+    InvalidOrderStatusTransition { from: OrderStatus, to: OrderStatus },
+    #[error("Too many login attempts, retry after {retry_after_seconds} seconds")]
+    TooManyLoginAttempts { retry_after_seconds: i64 },
+    #[error("Invalid password: {reason}")]
+    InvalidPassword { field: &'static str, reason: String },
+    #[error("Invalid cancel reason: {0}")]
+    InvalidCancelReason(String),
+    #[error("Invalid role: {0}")]
+    InvalidRole(String),
+    #[error("Area not found: {0}")]
+    InvalidArea(i32),
     #[error(transparent)]
     SqlxError(#[from] sqlx::Error),
 }
 
+/// リクエストの項目ごとのエラーを表す構造体
+///
+/// `field` - リクエストの項目名
+/// `message` - エラーの内容
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    /// 新しい項目ごとのエラーを作成する
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl AppError {
+    /// クライアントが判別に使う、変更されないエラーコードを返す
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest => "bad_request",
+            AppError::Unauthorized => "unauthorized",
+            AppError::Forbidden => "forbidden",
+            AppError::NotFound => "not_found",
+            AppError::Conflict => "conflict",
+            AppError::InternalServerError => "internal_server_error",
+            AppError::InvalidRequest(_) => "invalid_request",
+            AppError::ValidationFailed(_) => "validation_failed",
+            AppError::InvalidOrderStatus(_) => "invalid_order_status",
+            AppError::InvalidOrderStatusTransition { .. } => "invalid_order_status_transition",
+            AppError::TooManyLoginAttempts { .. } => "too_many_login_attempts",
+            AppError::InvalidPassword { .. } => "invalid_password",
+            AppError::InvalidCancelReason(_) => "invalid_cancel_reason",
+            AppError::InvalidRole(_) => "invalid_role",
+            AppError::InvalidArea(_) => "invalid_area",
+            AppError::SqlxError(err) => match sqlx_error_kind(err) {
+                SqlxErrorKind::RowNotFound => "not_found",
+                SqlxErrorKind::UniqueViolation => "conflict",
+                SqlxErrorKind::Other => "internal_server_error",
+            },
+        }
+    }
+
+    /// リクエストの項目ごとのエラーを返す
+    pub fn field_errors(&self) -> Vec<FieldError> {
+        match self {
+            AppError::ValidationFailed(errors) => errors.clone(),
+            AppError::InvalidOrderStatus(status) => {
+                vec![FieldError::new("status", format!("unknown status: {}", status))]
+            }
+            AppError::InvalidPassword { field, reason } => vec![FieldError::new(field, reason.as_str())],
+            AppError::InvalidCancelReason(reason) => {
+                vec![FieldError::new("reason", format!("unknown reason: {}", reason))]
+            }
+            AppError::InvalidRole(role) => {
+                vec![FieldError::new("role", format!("unknown role: {}", role))]
+            }
+            AppError::InvalidArea(area_id) => {
+                vec![FieldError::new("area_id", format!("area {} does not exist", area_id))]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// クライアントに返すメッセージを返す
+    ///
+    /// データベースエラーの場合は内部の詳細を返さず、ステータスコードの説明を返す
+    fn message(&self) -> String {
+        match self {
+            AppError::SqlxError(_) => self
+                .status_code()
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
 /// エラーレスポンスの構造体
+///
+/// `code` - エラーコード
+/// `message` - エラーの内容
+/// `fields` - リクエストの項目ごとのエラー（ない場合は省略）
 #[derive(Serialize)]
 struct ErrorResponse {
+    code: &'static str,
     message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldError>,
 }
 
 impl ResponseError for AppError {
+    /// エラーに対応するステータスコードを返す
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest
+            | AppError::InvalidRequest(_)
+            | AppError::ValidationFailed(_)
+            | AppError::InvalidOrderStatus(_)
+            | AppError::InvalidPassword { .. }
+            | AppError::InvalidCancelReason(_)
+            | AppError::InvalidRole(_)
+            | AppError::InvalidArea(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::Conflict | AppError::InvalidOrderStatusTransition { .. } => {
+                StatusCode::CONFLICT
+            }
+            AppError::TooManyLoginAttempts { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::SqlxError(err) => match sqlx_error_kind(err) {
+                SqlxErrorKind::RowNotFound => StatusCode::NOT_FOUND,
+                SqlxErrorKind::UniqueViolation => StatusCode::CONFLICT,
+                SqlxErrorKind::Other => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        }
+    }
+
     /// エラーレスポンスを生成する
     fn error_response(&self) -> HttpResponse {
-        let error_message = self.to_string();
+        let status_code = self.status_code();
+        if status_code == StatusCode::INTERNAL_SERVER_ERROR {
+            error!("{}", self);
+        }
+
         let error_response = ErrorResponse {
-            message: error_message,
+            code: self.code(),
+            message: self.message(),
+            fields: self.field_errors(),
         };
 
-        match *self {
-            AppError::BadRequest => HttpResponse::BadRequest().json(error_response),
-            AppError::Unauthorized => HttpResponse::Unauthorized().json(error_response),
-            AppError::NotFound => HttpResponse::NotFound().json(error_response),
-            AppError::Conflict => HttpResponse::Conflict().json(error_response),
-            AppError::InternalServerError => {
-                HttpResponse::InternalServerError().json(error_response)
-            }
-            AppError::SqlxError(_) => HttpResponse::InternalServerError().json(error_response),
+        let mut response = HttpResponse::build(status_code);
+        if let AppError::TooManyLoginAttempts {
+            retry_after_seconds,
+        } = self
+        {
+            response.insert_header(("Retry-After", retry_after_seconds.to_string()));
         }
+        response.json(error_response)
     }
-}
\ No newline at end of file
+}
+
+/// レスポンスの判定に使うデータベースエラーの種類
+enum SqlxErrorKind {
+    RowNotFound,
+    UniqueViolation,
+    Other,
+}
+
+/// データベースエラーの種類を判定する
+fn sqlx_error_kind(err: &sqlx::Error) -> SqlxErrorKind {
+    // MySQL の ER_DUP_ENTRY
+    const ER_DUP_ENTRY: u16 = 1062;
+
+    match err {
+        sqlx::Error::RowNotFound => SqlxErrorKind::RowNotFound,
+        sqlx::Error::Database(db_err)
+            if db_err
+                .try_downcast_ref::<MySqlDatabaseError>()
+                .is_some_and(|mysql_err| mysql_err.number() == ER_DUP_ENTRY) =>
+        {
+            SqlxErrorKind::UniqueViolation
+        }
+        _ => SqlxErrorKind::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::to_bytes;
+
+    use super::*;
+
+    async fn response_body(err: AppError) -> (StatusCode, serde_json::Value) {
+        let response = err.error_response();
+        let status = response.status();
+        let body = to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn maps_row_not_found_to_404() {
+        let (status, body) = response_body(AppError::SqlxError(sqlx::Error::RowNotFound)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["message"], "Not Found");
+    }
+
+    #[actix_rt::test]
+    async fn hides_internal_error_details() {
+        let (status, body) = response_body(AppError::SqlxError(sqlx::Error::PoolTimedOut)).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "internal_server_error");
+        assert_eq!(body["message"], "Internal Server Error");
+        assert!(body.get("fields").is_none());
+    }
+
+    #[actix_rt::test]
+    async fn includes_field_errors() {
+        let (status, body) = response_body(AppError::InvalidPassword {
+            field: "new_password",
+            reason: "must contain a digit".to_string(),
+        })
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_password");
+        assert_eq!(body["fields"][0]["field"], "new_password");
+        assert_eq!(body["fields"][0]["message"], "must contain a digit");
+    }
+
+    #[actix_rt::test]
+    async fn sets_retry_after_header() {
+        let response = AppError::TooManyLoginAttempts {
+            retry_after_seconds: 30,
+        }
+        .error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "30");
+    }
+}
@@ -1,16 +1,30 @@
+use std::env;
 use std::sync::Arc;
+use std::time::Duration;
 
 use actix_cors::Cors;
-use actix_web::{web, App, HttpServer};
+use actix_web::{rt, web, App, HttpServer};
+use api::auth_handler::TrustedProxies;
 use api::{
     auth_handler, health_check_handler, map_handler, order_handler, result_handler,
     tow_truck_handler,
 };
 use domains::map_service::MapService;
 use domains::{
-    auth_service::AuthService, order_service::OrderService, tow_truck_service::TowTruckService,
+    auth_service::{AuthConfig, AuthService},
+    clock::SystemClock,
+    login_throttle::LoginThrottleConfig,
+    order_service::OrderService,
+    password_policy::PasswordPolicy,
+    tow_truck_service::TowTruckService,
 };
+use errors::AppError;
+use log::{error, info};
 use middlewares::auth_middleware::AuthMiddleware;
+use middlewares::catch_panic_middleware::CatchPanicMiddleware;
+use models::graph::GraphCache;
+use models::tow_truck::LocationPlausibilityPolicy;
+use models::user::{Role, SessionPolicy};
 use repositories::auth_repository::AuthRepositoryImpl;
 use repositories::map_repository::MapRepositoryImpl;
 use repositories::order_repository::OrderRepositoryImpl;
@@ -36,14 +50,98 @@ async fn main() -> std::io::Result<()> {
         port = 18080;
     }
 
+    // エリアごとのグラフキャッシュを全ワーカーで共有する
+    // 環境変数 `CONTRACTION_HIERARCHY_MIN_NODES` を指定した場合、そのノード数以上のエリアでは縮約階層を構築する
+    let contraction_hierarchy_min_nodes = env::var("CONTRACTION_HIERARCHY_MIN_NODES")
+        .ok()
+        .and_then(|value| value.parse().ok());
+    let graph_cache = Arc::new(GraphCache::new(contraction_hierarchy_min_nodes));
+
+    // レッカー車が1秒あたりに進むグラフ上の距離（環境変数 `TOW_TRUCK_SPEED` で変更可能）
+    let tow_truck_speed = env::var("TOW_TRUCK_SPEED")
+        .ok()
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|speed| *speed > 0.0)
+        .unwrap_or(1.0);
+    // 位置の更新が現実的な移動かどうかの判定（環境変数 `LOCATION_MAX_SPEED` を指定した場合のみ有効）
+    // 前回の位置から `LOCATION_MAX_SPEED` × 経過秒数 + `LOCATION_GRACE_DISTANCE` を超える移動は implausible として履歴に記録される
+    let location_plausibility = env::var("LOCATION_MAX_SPEED")
+        .ok()
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|max_speed| *max_speed > 0.0)
+        .map(|max_speed| LocationPlausibilityPolicy {
+            max_speed,
+            grace_distance: env::var("LOCATION_GRACE_DISTANCE")
+                .ok()
+                .and_then(|value| value.parse::<i32>().ok())
+                .filter(|distance| *distance >= 0)
+                .unwrap_or(0),
+        });
+
+    // セッションの有効期間（環境変数 `SESSION_LIFETIME_SECONDS`、`SESSION_IDLE_TIMEOUT_SECONDS` で変更可能）
+    let session_policy = SessionPolicy {
+        lifetime: chrono::Duration::seconds(env_positive("SESSION_LIFETIME_SECONDS", 24 * 60 * 60)),
+        idle_timeout: chrono::Duration::seconds(env_positive(
+            "SESSION_IDLE_TIMEOUT_SECONDS",
+            2 * 60 * 60,
+        )),
+    };
+    // 期限切れのセッションを削除する間隔（環境変数 `SESSION_CLEANUP_INTERVAL_SECONDS` で変更可能）
+    let session_cleanup_interval =
+        Duration::from_secs(env_positive("SESSION_CLEANUP_INTERVAL_SECONDS", 5 * 60) as u64);
+
+    // ログイン試行の制限（環境変数 `LOGIN_*` で変更可能）
+    // 同じIPアドレスから複数のユーザーがログインする場合を考慮し、IPアドレスごとの制限は緩くする
+    let username_throttle = LoginThrottleConfig {
+        free_attempts: env_positive("LOGIN_USERNAME_FREE_ATTEMPTS", 3) as u32,
+        lockout_attempts: env_positive("LOGIN_USERNAME_LOCKOUT_ATTEMPTS", 10) as u32,
+        backoff_base: chrono::Duration::seconds(env_positive("LOGIN_BACKOFF_BASE_SECONDS", 1)),
+        lockout_duration: chrono::Duration::seconds(env_positive("LOGIN_LOCKOUT_SECONDS", 15 * 60)),
+    };
+    let ip_throttle = LoginThrottleConfig {
+        free_attempts: env_positive("LOGIN_IP_FREE_ATTEMPTS", 20) as u32,
+        lockout_attempts: env_positive("LOGIN_IP_LOCKOUT_ATTEMPTS", 100) as u32,
+        ..username_throttle
+    };
+    // X-Real-IP ヘッダーを信頼するプロキシ（環境変数 `TRUSTED_PROXIES` にカンマ区切りのIPアドレスまたはCIDR表記で指定）
+    // 指定しない場合はヘッダーを無視し、接続元のアドレスをリクエスト元のIPアドレスとする
+    let trusted_proxies = web::Data::new(TrustedProxies::parse(
+        &env::var("TRUSTED_PROXIES").unwrap_or_default(),
+    ));
+
+    // パスワードの強度（環境変数 `PASSWORD_*` で変更可能）
+    let password_policy = PasswordPolicy {
+        min_length: env_positive("PASSWORD_MIN_LENGTH", 8) as usize,
+        max_length: env_positive("PASSWORD_MAX_LENGTH", 128) as usize,
+        require_letter: env_flag("PASSWORD_REQUIRE_LETTER"),
+        require_digit: env_flag("PASSWORD_REQUIRE_DIGIT"),
+        require_symbol: env_flag("PASSWORD_REQUIRE_SYMBOL"),
+    };
+    // パスワードリセット用のトークンの有効期間（環境変数 `PASSWORD_RESET_TOKEN_SECONDS` で変更可能）
+    let password_reset_token_lifetime =
+        chrono::Duration::seconds(env_positive("PASSWORD_RESET_TOKEN_SECONDS", 60 * 60));
+
     // サービスの初期化
-    let auth_service = web::Data::new(AuthService::new(AuthRepositoryImpl::new(pool.clone())));
-    let auth_service_for_middleware =
-        Arc::new(AuthService::new(AuthRepositoryImpl::new(pool.clone())));
+    // 認証サービスはログイン試行の記録を共有するため、ハンドラーとミドルウェアで同じインスタンスを使う
+    let auth_service_for_middleware = Arc::new(AuthService::new(
+        AuthRepositoryImpl::new(pool.clone()),
+        AuthConfig {
+            session_policy,
+            username_throttle,
+            ip_throttle,
+            password_policy,
+            password_reset_token_lifetime,
+        },
+        Arc::new(SystemClock),
+    ));
+    let auth_service = web::Data::from(auth_service_for_middleware.clone());
     let tow_truck_service = web::Data::new(TowTruckService::new(
         TowTruckRepositoryImpl::new(pool.clone()),
         OrderRepositoryImpl::new(pool.clone()),
         MapRepositoryImpl::new(pool.clone()),
+        graph_cache.clone(),
+        tow_truck_speed,
+        location_plausibility,
     ));
     let order_service = web::Data::new(OrderService::new(
         OrderRepositoryImpl::new(pool.clone()),
@@ -51,7 +149,24 @@ async fn main() -> std::io::Result<()> {
         AuthRepositoryImpl::new(pool.clone()),
         MapRepositoryImpl::new(pool.clone()),
     ));
-    let map_service = web::Data::new(MapService::new(MapRepositoryImpl::new(pool.clone())));
+    let map_service = web::Data::new(MapService::new(
+        MapRepositoryImpl::new(pool.clone()),
+        graph_cache.clone(),
+    ));
+
+    // 期限切れのセッションをバックグラウンドで定期的に削除する
+    let auth_service_for_cleanup = auth_service_for_middleware.clone();
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(session_cleanup_interval);
+        loop {
+            interval.tick().await;
+            match auth_service_for_cleanup.delete_expired_sessions().await {
+                Ok(0) => {}
+                Ok(deleted) => info!("期限切れのセッションを{}件削除しました", deleted),
+                Err(err) => error!("期限切れのセッションの削除に失敗しました: {:?}", err),
+            }
+        }
+    });
 
     // HTTPサーバーの起動
     HttpServer::new(move || {
@@ -74,6 +189,19 @@ async fn main() -> std::io::Result<()> {
             .app_data(auth_service.clone())
             .app_data(order_service.clone())
             .app_data(map_service.clone())
+            .app_data(trusted_proxies.clone())
+            // リクエストの形式の誤りも他のエラーと同じ形式で返す
+            .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                AppError::InvalidRequest(err.to_string()).into()
+            }))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                AppError::InvalidRequest(err.to_string()).into()
+            }))
+            .app_data(web::PathConfig::default().error_handler(|err, _| {
+                AppError::InvalidRequest(err.to_string()).into()
+            }))
+            // パニックを 500 に変換する（CORS ヘッダーを付けるため cors より内側に置く）
+            .wrap(CatchPanicMiddleware)
             .wrap(cors)
             .service(
                 web::scope("/api")
@@ -86,8 +214,9 @@ async fn main() -> std::io::Result<()> {
                             .route(web::get().to(result_handler::result_handler)),
                     )
                     .service(
-                        web::resource("/register")
-                            .route(web::post().to(auth_handler::register_handler)),
+                        web::resource("/register").route(
+                            web::post().to(auth_handler::register_handler::<AuthRepositoryImpl>),
+                        ),
                     )
                     .service(
                         web::resource("/login").route(web::post().to(auth_handler::login_handler)),
@@ -96,25 +225,87 @@ async fn main() -> std::io::Result<()> {
                         web::resource("/logout")
                             .route(web::post().to(auth_handler::logout_handler)),
                     )
+                    .service(auth_handler::session_scope(auth_service_for_middleware.clone()))
+                    .service(
+                        web::scope("/password")
+                            .service(
+                                web::resource("/change")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Client, Role::Dispatcher, Role::Driver, Role::Admin],
+                                    ))
+                                    .route(web::post().to(auth_handler::change_password_handler)),
+                            )
+                            .service(
+                                web::resource("/reset")
+                                    .route(web::post().to(auth_handler::reset_password_handler)),
+                            )
+                            .service(
+                                web::resource("/reset/{user_id}")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Admin],
+                                    ))
+                                    .route(web::post().to(
+                                        auth_handler::issue_password_reset_token_handler,
+                                    )),
+                            ),
+                    )
                     .service(
                         web::resource("/user_image/{user_id}")
                             .route(web::get().to(auth_handler::user_profile_image_handler)),
                     )
                     .service(
                         web::scope("/tow_truck")
-                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
-                            .service(web::resource("/list").route(
-                                web::get().to(tow_truck_handler::get_paginated_tow_trucks_handler),
+                            .wrap(AuthMiddleware::new(
+                                auth_service_for_middleware.clone(),
+                                &[Role::Dispatcher, Role::Driver],
                             ))
                             .service(
-                                web::resource("/location").route(
-                                    web::post().to(tow_truck_handler::update_location_handler),
-                                ),
+                                web::resource("/list")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Dispatcher],
+                                    ))
+                                    .route(web::get().to(
+                                        tow_truck_handler::get_paginated_tow_trucks_handler,
+                                    )),
                             )
-                            .service(web::resource("/nearest").route(
-                                web::get().to(
-                                    tow_truck_handler::get_nearest_available_tow_trucks_handler,
-                                ),
+                            .service(
+                                web::resource("/location")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Driver],
+                                    ))
+                                    .route(
+                                        web::post().to(tow_truck_handler::update_location_handler),
+                                    ),
+                            )
+                            .service(
+                                web::resource("/nearest")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Dispatcher],
+                                    ))
+                                    .route(web::get().to(
+                                        tow_truck_handler::get_nearest_available_tow_trucks_handler,
+                                    )),
+                            )
+                            .service(
+                                web::resource("/nearest/list")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Dispatcher],
+                                    ))
+                                    .route(web::get().to(
+                                        tow_truck_handler::get_nearest_available_tow_truck_list_handler,
+                                    )),
+                            )
+                            .service(web::resource("/{id}/route").route(
+                                web::get().to(tow_truck_handler::get_tow_truck_route_handler),
+                            ))
+                            .service(web::resource("/{id}/history").route(
+                                web::get().to(tow_truck_handler::get_location_history_handler),
                             ))
                             .service(
                                 web::resource("/{id}")
@@ -123,25 +314,67 @@ async fn main() -> std::io::Result<()> {
                     )
                     .service(
                         web::scope("/order")
-                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+                            .wrap(AuthMiddleware::new(
+                                auth_service_for_middleware.clone(),
+                                &[Role::Client, Role::Dispatcher, Role::Driver],
+                            ))
                             .service(
-                                web::resource("/list").route(
-                                    web::get().to(order_handler::get_paginated_orders_handler),
-                                ),
+                                web::resource("/list")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Dispatcher],
+                                    ))
+                                    .route(
+                                        web::get().to(order_handler::get_paginated_orders_handler),
+                                    ),
                             )
                             .service(
-                                web::resource("/status").route(
-                                    web::post().to(order_handler::update_order_status_handler),
-                                ),
+                                web::resource("/status")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Dispatcher, Role::Driver],
+                                    ))
+                                    .route(
+                                        web::post().to(order_handler::update_order_status_handler),
+                                    ),
                             )
                             .service(
-                                web::resource("/client").route(
-                                    web::post().to(order_handler::create_client_order_handler),
-                                ),
+                                web::resource("/complete")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Dispatcher, Role::Driver],
+                                    ))
+                                    .route(web::post().to(order_handler::complete_order_handler)),
+                            )
+                            .service(
+                                web::resource("/cancel")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Client, Role::Dispatcher],
+                                    ))
+                                    .route(web::post().to(order_handler::cancel_order_handler)),
+                            )
+                            .service(
+                                web::resource("/client")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Client],
+                                    ))
+                                    .route(
+                                        web::post().to(order_handler::create_client_order_handler),
+                                    ),
+                            )
+                            .service(
+                                web::resource("/dispatcher")
+                                    .wrap(AuthMiddleware::new(
+                                        auth_service_for_middleware.clone(),
+                                        &[Role::Dispatcher],
+                                    ))
+                                    .route(
+                                        web::post()
+                                            .to(order_handler::create_dispatcher_order_handler),
+                                    ),
                             )
-                            .service(web::resource("/dispatcher").route(
-                                web::post().to(order_handler::create_dispatcher_order_handler),
-                            ))
                             .service(
                                 web::resource("/{id}")
                                     .route(web::get().to(order_handler::get_order_handler)),
@@ -149,7 +382,10 @@ async fn main() -> std::io::Result<()> {
                     )
                     .service(
                         web::scope("/map")
-                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+                            .wrap(AuthMiddleware::new(
+                                auth_service_for_middleware.clone(),
+                                &[Role::Dispatcher],
+                            ))
                             .service(
                                 web::resource("/update_edge")
                                     .route(web::put().to(map_handler::update_edge_handler)),
@@ -161,4 +397,22 @@ async fn main() -> std::io::Result<()> {
     .workers(1)
     .run()
     .await
-}
\ No newline at end of file
+}
+
+/// 環境変数から正の整数を読み取る
+///
+/// 未設定または不正な値の場合は `default` を返す
+fn env_positive(key: &str, default: i64) -> i64 {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(default)
+}
+
+/// 環境変数から真偽値を読み取る
+///
+/// `true` または `1` の場合のみ `true` を返す
+fn env_flag(key: &str) -> bool {
+    matches!(env::var(key).as_deref(), Ok("true") | Ok("1"))
+}
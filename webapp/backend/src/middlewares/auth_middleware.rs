@@ -1,41 +1,58 @@
+use std::rc::Rc;
 use std::sync::Arc;
 
 use actix_web::{
-    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, FromRequest, HttpMessage, HttpRequest,
 };
 use futures_util::future::{ready, LocalBoxFuture, Ready};
 
 use crate::{
-    domains::auth_service::AuthService, repositories::auth_repository::AuthRepositoryImpl,
+    domains::auth_service::{AuthRepository, AuthService},
+    errors::AppError,
+    models::user::{AuthenticatedUser, Role},
+    repositories::auth_repository::AuthRepositoryImpl,
 };
 
 /// 認証ミドルウェアの構造体
 /// 
 /// `auth_service` - 認証サービスのインスタンス
-pub struct AuthMiddleware {
-    auth_service: Arc<AuthService<AuthRepositoryImpl>>,
+/// `allowed_roles` - アクセスを許可するロール
+pub struct AuthMiddleware<T: AuthRepository + std::fmt::Debug = AuthRepositoryImpl> {
+    auth_service: Arc<AuthService<T>>,
+    allowed_roles: &'static [Role],
 }
 
-impl AuthMiddleware {
+impl<T: AuthRepository + std::fmt::Debug> AuthMiddleware<T> {
     /// 新しい認証ミドルウェアを作成する
     /// 
     /// `auth_service` - 認証サービスのインスタンス
-    pub fn new(auth_service: Arc<AuthService<AuthRepositoryImpl>>) -> Self {
-        AuthMiddleware { auth_service }
+    /// `allowed_roles` - アクセスを許可するロール
+    ///
+    /// スコープとその中のリソースの両方に適用した場合、セッションの解決は外側で一度だけ行われ、
+    /// 内側ではロールの確認のみ行う
+    pub fn new(
+        auth_service: Arc<AuthService<T>>,
+        allowed_roles: &'static [Role],
+    ) -> Self {
+        AuthMiddleware {
+            auth_service,
+            allowed_roles,
+        }
     }
 }
 
-impl<S, B> Transform<S, ServiceRequest> for AuthMiddleware
+impl<S, B, T> Transform<S, ServiceRequest> for AuthMiddleware<T>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
+    T: AuthRepository + std::fmt::Debug + 'static,
 {
     type Response = ServiceResponse<B>;
     type Error = Error;
     type InitError = ();
-    type Transform = AuthMiddlewareMiddleware<S>;
+    type Transform = AuthMiddlewareMiddleware<S, T>;
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     /// 新しいトランスフォームを作成する
@@ -43,8 +60,9 @@ where
     /// `service` - 次のサービス
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(AuthMiddlewareMiddleware {
-            service,
+            service: Rc::new(service),
             auth_service: self.auth_service.clone(),
+            allowed_roles: self.allowed_roles,
         }))
     }
 }
@@ -53,15 +71,18 @@ where
 /// 
 /// `service` - 次のサービス
 /// `auth_service` - 認証サービスのインスタンス
-pub struct AuthMiddlewareMiddleware<S> {
-    service: S,
-    auth_service: Arc<AuthService<AuthRepositoryImpl>>,
+/// `allowed_roles` - アクセスを許可するロール
+pub struct AuthMiddlewareMiddleware<S, T: AuthRepository + std::fmt::Debug> {
+    service: Rc<S>,
+    auth_service: Arc<AuthService<T>>,
+    allowed_roles: &'static [Role],
 }
 
-impl<S, B> Service<ServiceRequest> for AuthMiddlewareMiddleware<S>
+impl<S, B, T> Service<ServiceRequest> for AuthMiddlewareMiddleware<S, T>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     B: 'static,
+    T: AuthRepository + std::fmt::Debug + 'static,
 {
     type Response = ServiceResponse<B>;
     type Error = Error;
@@ -71,34 +92,278 @@ where
 
     /// リクエストを処理する
     /// 
+    /// セッションを認証済みのユーザーに解決してリクエストの extensions に格納し、
+    /// ロールが許可されていない場合は 403 を返す
+    /// 
     /// `req` - サービスリクエスト
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        // Authorization ヘッダーを取得
-        let auth_header = req
-            .headers()
-            .get("Authorization")
-            .and_then(|h| h.to_str().ok())
-            .map(|s| s.to_string());
-        let auth_header = Arc::new(auth_header);
-
+        let service = self.service.clone();
         let auth_service = self.auth_service.clone();
-        let fut = self.service.call(req);
+        let allowed_roles = self.allowed_roles;
 
         Box::pin(async move {
-            // トークンの検証
-            let is_valid_token = match &*auth_header {
-                Some(token) => auth_service.validate_session(token).await.is_ok(),
-                None => false,
+            // 外側のスコープで解決済みの場合はそれを使う
+            let resolved_user = req.extensions().get::<AuthenticatedUser>().cloned();
+            let authenticated_user = match resolved_user {
+                Some(authenticated_user) => authenticated_user,
+                None => {
+                    // Authorization ヘッダーを取得
+                    let auth_header = req
+                        .headers()
+                        .get("Authorization")
+                        .and_then(|h| h.to_str().ok())
+                        .map(|s| s.to_string());
+                    let token = auth_header.ok_or(AppError::Unauthorized)?;
+
+                    // トークンの検証
+                    match auth_service.authenticate_session(&token).await {
+                        Ok(authenticated_user) => authenticated_user,
+                        Err(AppError::Unauthorized)
+                        | Err(AppError::SqlxError(sqlx::Error::RowNotFound)) => {
+                            return Err(AppError::Unauthorized.into())
+                        }
+                        Err(err) => return Err(err.into()),
+                    }
+                }
             };
 
-            // トークンが有効な場合は次のサービスを呼び出し、無効な場合はエラーを返す
-            if is_valid_token {
-                fut.await
-            } else {
-                Err(actix_web::error::ErrorUnauthorized(
-                    "Invalid or missing token",
-                ))
+            if !allowed_roles
+                .iter()
+                .any(|role| authenticated_user.user.has_role(*role))
+            {
+                return Err(AppError::Forbidden.into());
             }
+
+            req.extensions_mut().insert(authenticated_user);
+            service.call(req).await
         })
     }
-}
\ No newline at end of file
+}
+
+/// 認証ミドルウェアが格納した認証済みのユーザーをハンドラーで受け取るための抽出器
+///
+/// 認証ミドルウェアを適用していないルートで使用した場合は `AppError::Unauthorized` を返す
+impl FromRequest for AuthenticatedUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<AuthenticatedUser>()
+                .cloned()
+                .ok_or(AppError::Unauthorized),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+    use chrono::{DateTime, Duration, Utc};
+
+    use super::*;
+    use crate::domains::auth_service::AuthConfig;
+    use crate::domains::clock::SystemClock;
+    use crate::domains::login_throttle::LoginThrottleConfig;
+    use crate::domains::password_policy::PasswordPolicy;
+    use crate::models::user::{Session, SessionPolicy, User};
+    use crate::repositories::in_memory::InMemoryRepository;
+
+    const VALID_TOKEN: &str = "valid-token";
+    const INVALID_TOKEN: &str = "invalid-token";
+    const EXPIRED_TOKEN: &str = "expired-token";
+    const IDLE_TOKEN: &str = "idle-token";
+    const STALE_TOKEN: &str = "stale-token";
+
+    /// ドライバーと、状態の異なるセッションを保持するリポジトリを用意する
+    fn auth_repository(now: DateTime<Utc>) -> InMemoryRepository {
+        let session = |id: i32, token: &str, is_valid: bool, created: i64, seen: i64| Session {
+            id,
+            user_id: 1,
+            session_token: token.to_string(),
+            is_valid,
+            created_at: now - Duration::minutes(created),
+            last_seen_at: now - Duration::minutes(seen),
+        };
+
+        let repository = InMemoryRepository::default();
+        {
+            let mut data = repository.data();
+            data.users.push(User {
+                id: 1,
+                username: "driver1".to_string(),
+                password: String::new(),
+                role: "driver".to_string(),
+            });
+            data.sessions = vec![
+                session(1, VALID_TOKEN, true, 0, 0),
+                session(2, INVALID_TOKEN, false, 0, 0),
+                session(3, EXPIRED_TOKEN, true, 25 * 60, 0),
+                session(4, IDLE_TOKEN, true, 3 * 60, 3 * 60),
+                session(5, STALE_TOKEN, true, 30, 30),
+            ];
+        }
+        repository
+    }
+
+    fn auth_service(repository: InMemoryRepository) -> Arc<AuthService<InMemoryRepository>> {
+        let session_policy = SessionPolicy {
+            lifetime: Duration::hours(24),
+            idle_timeout: Duration::hours(2),
+        };
+        let login_throttle = LoginThrottleConfig {
+            free_attempts: 3,
+            lockout_attempts: 10,
+            backoff_base: Duration::seconds(1),
+            lockout_duration: Duration::minutes(15),
+        };
+        let password_policy = PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+            require_letter: false,
+            require_digit: false,
+            require_symbol: false,
+        };
+        Arc::new(AuthService::new(
+            repository,
+            AuthConfig {
+                session_policy,
+                username_throttle: login_throttle,
+                ip_throttle: login_throttle,
+                password_policy,
+                password_reset_token_lifetime: Duration::hours(1),
+            },
+            Arc::new(SystemClock),
+        ))
+    }
+
+    /// `allowed_roles` を許可する認証ミドルウェアを適用したアプリケーションにリクエストを送り、ステータスコードを返す
+    async fn request_status(
+        auth_service: Arc<AuthService<InMemoryRepository>>,
+        allowed_roles: &'static [Role],
+        token: Option<&str>,
+    ) -> StatusCode {
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/api")
+                    .wrap(AuthMiddleware::new(auth_service, allowed_roles))
+                    .route(
+                        "/me",
+                        web::get().to(|user: AuthenticatedUser| async move {
+                            HttpResponse::Ok().body(user.user.username)
+                        }),
+                    ),
+            ),
+        )
+        .await;
+
+        let mut req = test::TestRequest::get().uri("/api/me");
+        if let Some(token) = token {
+            req = req.insert_header(("Authorization", token));
+        }
+        match test::try_call_service(&app, req.to_request()).await {
+            Ok(res) => res.status(),
+            Err(err) => err.as_response_error().status_code(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn accepts_valid_token() {
+        let status = request_status(
+            auth_service(auth_repository(Utc::now())),
+            &[Role::Driver],
+            Some(VALID_TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn rejects_missing_token() {
+        let status = request_status(
+            auth_service(auth_repository(Utc::now())),
+            &[Role::Driver],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn rejects_unknown_token() {
+        let status = request_status(
+            auth_service(auth_repository(Utc::now())),
+            &[Role::Driver],
+            Some("unknown-token"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn rejects_invalidated_token() {
+        let status = request_status(
+            auth_service(auth_repository(Utc::now())),
+            &[Role::Driver],
+            Some(INVALID_TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn rejects_token_past_lifetime() {
+        let status = request_status(
+            auth_service(auth_repository(Utc::now())),
+            &[Role::Driver],
+            Some(EXPIRED_TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn rejects_token_past_idle_timeout() {
+        let status = request_status(
+            auth_service(auth_repository(Utc::now())),
+            &[Role::Driver],
+            Some(IDLE_TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn rejects_role_not_allowed() {
+        let status = request_status(
+            auth_service(auth_repository(Utc::now())),
+            &[Role::Dispatcher],
+            Some(VALID_TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn updates_last_seen_time_of_stale_session() {
+        let before = Utc::now();
+        let repository = auth_repository(before);
+
+        let status = request_status(
+            auth_service(repository.clone()),
+            &[Role::Driver],
+            Some(STALE_TOKEN),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        let data = repository.data();
+        let session = data
+            .sessions
+            .iter()
+            .find(|session| session.session_token == STALE_TOKEN)
+            .unwrap();
+        assert!(session.last_seen_at >= before);
+    }
+}
@@ -0,0 +1,151 @@
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use futures_util::future::{ready, FutureExt, LocalBoxFuture, Ready};
+use log::error;
+
+use crate::errors::AppError;
+
+/// パニックを捕捉するミドルウェアの構造体
+///
+/// ハンドラーやその内側のミドルウェアでパニックが発生した場合、ワーカーを停止させずに
+/// ログに記録して 500 を返す
+pub struct CatchPanicMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for CatchPanicMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CatchPanicMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    /// 新しいトランスフォームを作成する
+    ///
+    /// `service` - 次のサービス
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CatchPanicMiddlewareMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+/// パニックを捕捉するミドルウェアの内部構造体
+///
+/// `service` - 次のサービス
+pub struct CatchPanicMiddlewareMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CatchPanicMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    /// リクエストを処理する
+    ///
+    /// 次のサービスの呼び出しと処理中のパニックを捕捉し、
+    /// パニックした場合はリクエストとパニックの内容をログに記録して 500 を返す
+    ///
+    /// `req` - サービスリクエスト
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let method = req.method().clone();
+        let path = req.path().to_string();
+
+        Box::pin(async move {
+            // サービスの呼び出し自体のパニックも捕捉するため、async ブロックごと包む
+            match AssertUnwindSafe(async move { service.call(req).await })
+                .catch_unwind()
+                .await
+            {
+                Ok(result) => result,
+                Err(payload) => {
+                    error!(
+                        "Panic while handling {} {}: {}",
+                        method,
+                        path,
+                        panic_message(payload.as_ref())
+                    );
+                    Err(AppError::InternalServerError.into())
+                }
+            }
+        })
+    }
+}
+
+/// パニックのペイロードからメッセージを取り出す
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test as actix_test, web, App, HttpResponse};
+
+    use super::*;
+
+    async fn panic_handler() -> HttpResponse {
+        panic!("handler panicked")
+    }
+
+    async fn ok_handler() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_rt::test]
+    async fn turns_panic_into_internal_server_error() {
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(CatchPanicMiddleware)
+                .route("/panic", web::get().to(panic_handler))
+                .route("/ok", web::get().to(ok_handler)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/panic").to_request();
+        let status = match actix_test::try_call_service(&app, req).await {
+            Ok(res) => res.status(),
+            Err(err) => err.as_response_error().status_code(),
+        };
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        // パニックの後も同じアプリケーションでリクエストを処理できる
+        let req = actix_test::TestRequest::get().uri("/ok").to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn extracts_panic_message() {
+        let payload: Box<dyn Any + Send> = Box::new("static message");
+        assert_eq!(panic_message(payload.as_ref()), "static message");
+
+        let payload: Box<dyn Any + Send> = Box::new(format!("formatted {}", 1));
+        assert_eq!(panic_message(payload.as_ref()), "formatted 1");
+
+        let payload: Box<dyn Any + Send> = Box::new(1);
+        assert_eq!(panic_message(payload.as_ref()), "unknown panic");
+    }
+}
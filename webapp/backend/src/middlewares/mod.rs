@@ -1 +1,2 @@
 pub mod auth_middleware;
+pub mod catch_panic_middleware;
@@ -0,0 +1,498 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::graph::Graph;
+
+/// 証人探索（ショートカットが不要かどうかの確認）で確定させるノード数の上限
+///
+/// 上限に達した場合は迂回路が見つからなかったものとしてショートカットを追加するため、
+/// 値を小さくしても結果の正しさは変わらず、インデックスが大きくなるだけである
+const WITNESS_SEARCH_LIMIT: usize = 64;
+
+/// 1ノードの縮約で追加するショートカット数の上限
+///
+/// 優先度が最も低いノードでもこの上限を超える場合は縮約を打ち切り、残ったノードをコアとして
+/// 縮約せずに保持する。ランダムな長距離エッジが多いグラフでショートカットが爆発的に増えるのを防ぐ
+const MAX_SHORTCUTS_PER_NODE: usize = 32;
+
+/// 縮約階層（Contraction Hierarchies）のインデックスを表す構造体
+///
+/// 重要度の低いノードから順に縮約し、縮約済みノードを迂回するショートカットを追加したグラフを保持する。
+/// 検索時は始点と目的地の双方から「より後に縮約されたノード」へ向かうエッジだけを辿るため、
+/// 大きなグラフでも探索するノード数が少なく済む。
+/// 縮約を打ち切ったコアのノードは、コア内の全てのエッジを上向きエッジとして保持する
+///
+/// `upward_edges` - ノードごとの、より後に縮約されたノードへのエッジ（ショートカットを含む）
+#[derive(Debug)]
+pub struct ContractionHierarchy {
+    upward_edges: HashMap<i32, Vec<(i32, i32)>>,
+}
+
+impl ContractionHierarchy {
+    /// グラフから縮約階層のインデックスを構築する
+    pub fn build(graph: &Graph) -> Self {
+        // 自己ループを除き、同じノード間の複数エッジは最小の重みにまとめた隣接リストを作成
+        let mut adjacency: HashMap<i32, HashMap<i32, i32>> = HashMap::new();
+        for node_id in graph.nodes.keys() {
+            adjacency.entry(*node_id).or_default();
+        }
+        for edge in graph.edges.values().flatten() {
+            if edge.node_a_id == edge.node_b_id {
+                continue;
+            }
+            for (from, to) in [(edge.node_a_id, edge.node_b_id), (edge.node_b_id, edge.node_a_id)] {
+                let weight = adjacency.entry(from).or_default().entry(to).or_insert(edge.weight);
+                *weight = (*weight).min(edge.weight);
+            }
+        }
+
+        // 縮約の優先度（追加されるショートカット数 - 次数 + 縮約済みの隣接ノード数）が低い順に縮約する
+        let mut contracted_neighbors: HashMap<i32, i32> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        for (&node_id, edges) in &adjacency {
+            // 初期の優先度は次数から簡易に見積もり、正確な値は取り出した時点で計算する
+            let degree = edges.len() as i32;
+            heap.push(Reverse((degree * (degree - 1) / 2 - degree, node_id)));
+        }
+
+        let mut upward_edges = HashMap::new();
+        while let Some(Reverse((_, node_id))) = heap.pop() {
+            // 優先度は隣接ノードの縮約により変化するため、取り出した時点で再計算する
+            let shortcuts = find_shortcuts(&adjacency, node_id);
+            let current_priority = priority(
+                &adjacency,
+                node_id,
+                &shortcuts,
+                *contracted_neighbors.get(&node_id).unwrap_or(&0),
+            );
+            if let Some(Reverse((next_priority, _))) = heap.peek() {
+                if current_priority > *next_priority {
+                    heap.push(Reverse((current_priority, node_id)));
+                    continue;
+                }
+            }
+
+            // 残りのノードはコアとして、互いへのエッジを全て保持する
+            if shortcuts.len() > MAX_SHORTCUTS_PER_NODE {
+                for (core_node_id, edges) in adjacency.drain() {
+                    upward_edges.insert(core_node_id, edges.into_iter().collect());
+                }
+                break;
+            }
+
+            let neighbors = adjacency.remove(&node_id).unwrap_or_default();
+            for neighbor_id in neighbors.keys() {
+                if let Some(edges) = adjacency.get_mut(neighbor_id) {
+                    edges.remove(&node_id);
+                }
+                *contracted_neighbors.entry(*neighbor_id).or_default() += 1;
+            }
+            for (node_a_id, node_b_id, weight) in shortcuts {
+                for (from, to) in [(node_a_id, node_b_id), (node_b_id, node_a_id)] {
+                    let current = adjacency.entry(from).or_default().entry(to).or_insert(weight);
+                    *current = (*current).min(weight);
+                }
+            }
+
+            // 縮約時点で残っている隣接ノードは、全てこのノードより後に縮約される
+            upward_edges.insert(node_id, neighbors.into_iter().collect());
+        }
+
+        ContractionHierarchy { upward_edges }
+    }
+
+    /// 2つのノード間の最短距離を双方向探索で計算する
+    ///
+    /// 戻り値: 最短距離（目的地ノードに到達できない場合は `None`）
+    pub fn shortest_path(&self, from_node_id: i32, to_node_id: i32) -> Option<i32> {
+        let mut distances = [HashMap::new(), HashMap::new()];
+        let mut heaps = [BinaryHeap::new(), BinaryHeap::new()];
+        for (direction, node_id) in [from_node_id, to_node_id].into_iter().enumerate() {
+            distances[direction].insert(node_id, 0);
+            heaps[direction].push(Reverse((0, node_id)));
+        }
+
+        let mut best = i32::MAX;
+        loop {
+            // 推定コストが小さい方向の探索を1ステップ進める
+            let direction = match (heaps[0].peek(), heaps[1].peek()) {
+                (Some(Reverse(forward)), Some(Reverse(backward))) => {
+                    if forward <= backward {
+                        0
+                    } else {
+                        1
+                    }
+                }
+                (Some(_), None) => 0,
+                (None, Some(_)) => 1,
+                (None, None) => break,
+            };
+            let Some(Reverse((cost, position))) = heaps[direction].pop() else {
+                break;
+            };
+
+            // 残りのノードを経由しても現在の最短距離より短くならない場合、探索を終了
+            if cost >= best {
+                break;
+            }
+            if cost > *distances[direction].get(&position).unwrap_or(&i32::MAX) {
+                continue;
+            }
+
+            // 反対方向の探索で到達済みのノードであれば、そこで合流する経路を候補にする
+            if let Some(other_cost) = distances[1 - direction].get(&position) {
+                best = best.min(cost.saturating_add(*other_cost));
+            }
+
+            self.relax(position, cost, &mut distances[direction], &mut heaps[direction]);
+        }
+
+        (best != i32::MAX).then_some(best)
+    }
+
+    /// 1つの始点ノードから複数の目的地ノードまでの最短距離をまとめて計算する
+    ///
+    /// 始点ノードからの上向き探索は1回だけ行い、目的地ノードごとに逆方向の探索で合流点を求める
+    ///
+    /// 戻り値: 目的地ノードIDをキー、最短距離を値とするマップ（到達できないノードは含まれない）
+    pub fn shortest_paths(&self, from_node_id: i32, to_node_ids: &[i32]) -> HashMap<i32, i32> {
+        let forward = self.upward_search(from_node_id);
+
+        let mut results = HashMap::new();
+        for &to_node_id in to_node_ids {
+            let mut distances = HashMap::new();
+            let mut heap = BinaryHeap::new();
+            distances.insert(to_node_id, 0);
+            heap.push(Reverse((0, to_node_id)));
+
+            let mut best = i32::MAX;
+            while let Some(Reverse((cost, position))) = heap.pop() {
+                if cost >= best {
+                    break;
+                }
+                if cost > *distances.get(&position).unwrap_or(&i32::MAX) {
+                    continue;
+                }
+                if let Some(forward_cost) = forward.get(&position) {
+                    best = best.min(cost.saturating_add(*forward_cost));
+                }
+                self.relax(position, cost, &mut distances, &mut heap);
+            }
+
+            if best != i32::MAX {
+                results.insert(to_node_id, best);
+            }
+        }
+
+        results
+    }
+
+    /// 始点ノードから上向きエッジだけを辿り、到達できる全てのノードまでの距離を計算する
+    fn upward_search(&self, from_node_id: i32) -> HashMap<i32, i32> {
+        let mut distances = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        distances.insert(from_node_id, 0);
+        heap.push(Reverse((0, from_node_id)));
+
+        while let Some(Reverse((cost, position))) = heap.pop() {
+            if cost > *distances.get(&position).unwrap_or(&i32::MAX) {
+                continue;
+            }
+            self.relax(position, cost, &mut distances, &mut heap);
+        }
+
+        distances
+    }
+
+    /// ノードの上向きエッジを辿り、より短い距離が見つかった隣接ノードをヒープにプッシュする
+    fn relax(
+        &self,
+        position: i32,
+        cost: i32,
+        distances: &mut HashMap<i32, i32>,
+        heap: &mut BinaryHeap<Reverse<(i32, i32)>>,
+    ) {
+        if let Some(edges) = self.upward_edges.get(&position) {
+            for &(next_position, weight) in edges {
+                let next_cost = cost.saturating_add(weight);
+                if next_cost < *distances.get(&next_position).unwrap_or(&i32::MAX) {
+                    distances.insert(next_position, next_cost);
+                    heap.push(Reverse((next_cost, next_position)));
+                }
+            }
+        }
+    }
+}
+
+/// ノードを縮約する優先度を計算する（値が小さいほど先に縮約する）
+fn priority(
+    adjacency: &HashMap<i32, HashMap<i32, i32>>,
+    node_id: i32,
+    shortcuts: &[(i32, i32, i32)],
+    contracted_neighbors: i32,
+) -> i32 {
+    let degree = adjacency.get(&node_id).map_or(0, |edges| edges.len()) as i32;
+    shortcuts.len() as i32 - degree + contracted_neighbors
+}
+
+/// ノードを縮約する際に必要となるショートカットを求める
+///
+/// 隣接ノードの組ごとに、縮約するノードを経由しない同じ長さ以下の迂回路が見つからなければショートカットを追加する
+///
+/// 戻り値: ショートカットの両端のノードIDと重みの組のベクター
+fn find_shortcuts(
+    adjacency: &HashMap<i32, HashMap<i32, i32>>,
+    node_id: i32,
+) -> Vec<(i32, i32, i32)> {
+    let mut neighbors: Vec<(i32, i32)> = match adjacency.get(&node_id) {
+        Some(edges) => edges.iter().map(|(&id, &weight)| (id, weight)).collect(),
+        None => return Vec::new(),
+    };
+    neighbors.sort();
+
+    let mut shortcuts = Vec::new();
+    for (index, &(node_a_id, weight_a)) in neighbors.iter().enumerate() {
+        let targets = &neighbors[index + 1..];
+        let Some(max_weight_b) = targets.iter().map(|&(_, weight)| weight).max() else {
+            continue;
+        };
+
+        let witnesses = witness_search(
+            adjacency,
+            node_a_id,
+            node_id,
+            weight_a.saturating_add(max_weight_b),
+        );
+        for &(node_b_id, weight_b) in targets {
+            let via_weight = weight_a.saturating_add(weight_b);
+            let has_witness = witnesses
+                .get(&node_b_id)
+                .is_some_and(|&witness_weight| witness_weight <= via_weight);
+            if !has_witness {
+                shortcuts.push((node_a_id, node_b_id, via_weight));
+            }
+        }
+    }
+
+    shortcuts
+}
+
+/// 縮約するノードを除いたグラフ上で、始点ノードから `max_cost` 以内で到達できるノードまでの距離を計算する
+///
+/// 確定させるノード数が上限に達した場合はその時点で探索を打ち切る。
+/// 未確定の距離も実在する経路の長さであるため、迂回路の判定にそのまま用いる
+fn witness_search(
+    adjacency: &HashMap<i32, HashMap<i32, i32>>,
+    from_node_id: i32,
+    excluded_node_id: i32,
+    max_cost: i32,
+) -> HashMap<i32, i32> {
+    let mut distances = HashMap::new();
+    let mut heap = BinaryHeap::new();
+    distances.insert(from_node_id, 0);
+    heap.push(Reverse((0, from_node_id)));
+
+    let mut settled = 0;
+    while let Some(Reverse((cost, position))) = heap.pop() {
+        if cost > max_cost || settled >= WITNESS_SEARCH_LIMIT {
+            break;
+        }
+        if cost > *distances.get(&position).unwrap_or(&i32::MAX) {
+            continue;
+        }
+        settled += 1;
+
+        if let Some(edges) = adjacency.get(&position) {
+            for (&next_position, &weight) in edges {
+                if next_position == excluded_node_id {
+                    continue;
+                }
+                let next_cost = cost.saturating_add(weight);
+                if next_cost < *distances.get(&next_position).unwrap_or(&i32::MAX) {
+                    distances.insert(next_position, next_cost);
+                    heap.push(Reverse((next_cost, next_position)));
+                }
+            }
+        }
+    }
+
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use std::sync::RwLock;
+
+    use super::*;
+    use crate::models::graph::{rebuild_contraction_hierarchy, Edge, Node};
+
+    /// 格子状の道路網に近いランダムなグラフを生成する
+    ///
+    /// 格子の隣接ノード間の一部と、ランダムなノード間の一部にエッジを張るため、非連結な部分も含まれる
+    fn generate_random_graph(rng: &mut StdRng, width: i32, height: i32) -> Graph {
+        let mut graph = Graph::new();
+        let id = |x: i32, y: i32| y * width + x + 1;
+        for y in 0..height {
+            for x in 0..width {
+                graph.add_node(Node {
+                    id: id(x, y),
+                    x: x * 10 + rng.gen_range(0..5),
+                    y: y * 10 + rng.gen_range(0..5),
+                });
+            }
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                for (next_x, next_y) in [(x + 1, y), (x, y + 1)] {
+                    if next_x < width && next_y < height && rng.gen_bool(0.85) {
+                        graph.add_edge(Edge {
+                            node_a_id: id(x, y),
+                            node_b_id: id(next_x, next_y),
+                            weight: rng.gen_range(1..100),
+                        });
+                    }
+                }
+            }
+        }
+        for _ in 0..(width * height / 20) {
+            graph.add_edge(Edge {
+                node_a_id: rng.gen_range(1..=width * height),
+                node_b_id: rng.gen_range(1..=width * height),
+                weight: rng.gen_range(50..500),
+            });
+        }
+
+        graph
+    }
+
+    /// ランダムな始点・目的地の組について、縮約階層と通常のダイクストラ法の結果を比較する
+    ///
+    /// 戻り値: ダイクストラ法と縮約階層それぞれの合計探索時間
+    fn assert_matches_dijkstra(
+        rng: &mut StdRng,
+        graph: &Graph,
+        contraction_hierarchy: &ContractionHierarchy,
+        queries: usize,
+    ) -> (Duration, Duration) {
+        let node_count = graph.nodes.len() as i32;
+        let mut dijkstra_time = Duration::ZERO;
+        let mut contraction_hierarchy_time = Duration::ZERO;
+
+        for _ in 0..queries {
+            let from = rng.gen_range(1..=node_count);
+            let to = rng.gen_range(1..=node_count);
+
+            let started = Instant::now();
+            let expected = graph.shortest_paths(from, &[to]).get(&to).cloned();
+            dijkstra_time += started.elapsed();
+
+            let started = Instant::now();
+            let actual = contraction_hierarchy.shortest_path(from, to);
+            contraction_hierarchy_time += started.elapsed();
+
+            assert_eq!(actual, expected, "from {} to {}", from, to);
+        }
+
+        let from = rng.gen_range(1..=node_count);
+        let targets: Vec<i32> = (0..20).map(|_| rng.gen_range(1..=node_count)).collect();
+        assert_eq!(
+            contraction_hierarchy.shortest_paths(from, &targets),
+            graph.shortest_paths(from, &targets)
+        );
+
+        (dijkstra_time, contraction_hierarchy_time)
+    }
+
+    #[test]
+    fn matches_dijkstra_on_random_graphs() {
+        let mut rng = StdRng::seed_from_u64(2407);
+
+        for (width, height) in [(5, 5), (20, 10), (40, 40)] {
+            let graph = generate_random_graph(&mut rng, width, height);
+            let contraction_hierarchy = ContractionHierarchy::build(&graph);
+
+            assert_matches_dijkstra(&mut rng, &graph, &contraction_hierarchy, 200);
+
+            assert_eq!(graph.nodes.len(), (width * height) as usize);
+            assert!(!contraction_hierarchy.upward_edges.is_empty());
+        }
+    }
+
+    /// 実行時間に依存するため通常のテストからは除外する
+    ///
+    /// `cargo test --release -- --ignored faster_than_dijkstra` で実行する
+    #[test]
+    #[ignore]
+    fn faster_than_dijkstra_on_large_graph() {
+        let mut rng = StdRng::seed_from_u64(2407);
+        let graph = generate_random_graph(&mut rng, 40, 40);
+
+        let started = Instant::now();
+        let contraction_hierarchy = ContractionHierarchy::build(&graph);
+        let build_time = started.elapsed();
+
+        let (dijkstra_time, contraction_hierarchy_time) =
+            assert_matches_dijkstra(&mut rng, &graph, &contraction_hierarchy, 200);
+
+        println!(
+            "{} nodes: build {:?}, dijkstra {:?}, contraction hierarchy {:?}",
+            graph.nodes.len(),
+            build_time,
+            dijkstra_time,
+            contraction_hierarchy_time
+        );
+        // インデックス上の探索がダイクストラ法より速い（手元のリリースビルドでは5倍程度）
+        assert!(contraction_hierarchy_time < dijkstra_time);
+    }
+
+    #[test]
+    fn matches_dijkstra_after_edge_updates() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let graph = generate_random_graph(&mut rng, 15, 15);
+
+        // 同じグラフを複製し、インデックスを構築しない比較用のグラフとする
+        let mut plain = Graph::new();
+        for node in graph.nodes.values() {
+            plain.add_node(node.clone());
+        }
+        for edge in graph.edges.values().flatten() {
+            if edge.node_a_id < edge.node_b_id {
+                plain.add_edge(edge.clone());
+            }
+        }
+        let graph = RwLock::new(graph);
+        rebuild_contraction_hierarchy(&graph);
+
+        let node_count = plain.nodes.len() as i32;
+        for _ in 0..10 {
+            let node_a_id = rng.gen_range(1..=node_count);
+            let Some(edge) = plain.edges.get(&node_a_id).and_then(|edges| edges.first()) else {
+                continue;
+            };
+            let (node_b_id, weight) = (edge.node_b_id, rng.gen_range(0..1000));
+            graph.write().unwrap().update_edge_weight(node_a_id, node_b_id, weight);
+            plain.update_edge_weight(node_a_id, node_b_id, weight);
+
+            rebuild_contraction_hierarchy(&graph);
+            let graph = graph.read().unwrap();
+
+            for _ in 0..50 {
+                let from = rng.gen_range(1..=node_count);
+                let to = rng.gen_range(1..=node_count);
+                assert_eq!(
+                    graph.shortest_path(from, to),
+                    plain.shortest_path(from, to),
+                    "from {} to {}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+}
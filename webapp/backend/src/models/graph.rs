@@ -1,6 +1,11 @@
+use serde::Deserialize;
 use sqlx::FromRow;
-use std::collections::{HashMap, BinaryHeap};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::cmp::Ordering;
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+use std::thread;
+
+use super::contraction_hierarchy::ContractionHierarchy;
 
 #[derive(FromRow, Clone, Debug)]
 pub struct Node {
@@ -16,10 +21,44 @@ pub struct Edge {
     pub weight: i32,
 }
 
+/// 最短経路を表す構造体
+///
+/// `node_ids` - 始点から目的地までに通過するノードIDの列
+/// `weights` - 隣り合うノード間のエッジの重みの列（`node_ids` より1つ少ない）
+/// `cost` - 経路全体のコスト
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub node_ids: Vec<i32>,
+    pub weights: Vec<i32>,
+    pub cost: i32,
+}
+
+/// 最短経路の探索方法
+///
+/// `Dijkstra` - ダイクストラ法（既定）
+/// `AStar` - ノードの座標をヒューリスティックに用いるA*探索
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteAlgorithm {
+    #[default]
+    Dijkstra,
+    AStar,
+}
+
+/// グラフを表す構造体
+///
+/// `revision` - ノードやエッジが変更されるたびに進む版数（構築中にグラフが変更された縮約階層を破棄するために用いる）
+/// `uses_contraction_hierarchy` - 縮約階層のインデックスを使用するかどうか
+/// `rebuilding_contraction_hierarchy` - 縮約階層のインデックスをバックグラウンドで構築中かどうか
 #[derive(Debug)]
 pub struct Graph {
     pub nodes: HashMap<i32, Node>,
     pub edges: HashMap<i32, Vec<Edge>>,
+    heuristic_scale: OnceLock<f64>,
+    contraction_hierarchy: Option<ContractionHierarchy>,
+    revision: u64,
+    uses_contraction_hierarchy: bool,
+    rebuilding_contraction_hierarchy: bool,
 }
 
 impl Graph {
@@ -27,14 +66,27 @@ impl Graph {
         Graph {
             nodes: HashMap::new(),
             edges: HashMap::new(),
+            heuristic_scale: OnceLock::new(),
+            contraction_hierarchy: None,
+            revision: 0,
+            uses_contraction_hierarchy: false,
+            rebuilding_contraction_hierarchy: false,
         }
     }
 
+    /// ノードを追加する
+    ///
+    /// 構築済みの縮約階層のインデックスは破棄されるため、必要であれば再構築すること
     pub fn add_node(&mut self, node: Node) {
         self.nodes.insert(node.id, node);
+        self.invalidate();
     }
 
+    /// エッジを両方向に追加する
+    ///
+    /// 構築済みの縮約階層のインデックスは破棄されるため、必要であれば再構築すること
     pub fn add_edge(&mut self, edge: Edge) {
+        self.invalidate();
         self.edges
             .entry(edge.node_a_id)
             .or_default()
@@ -51,53 +103,238 @@ impl Graph {
             .push(reverse_edge);
     }
 
+    /// 2つのノード間のエッジの重みを両方向とも更新する
+    ///
+    /// 該当するエッジが存在しない場合は何もしない。
+    /// 重みが変わった場合、構築済みの縮約階層のインデックスは破棄され、
+    /// 再構築されるまでの最短距離の計算はダイクストラ法で行われる
+    pub fn update_edge_weight(&mut self, node_a_id: i32, node_b_id: i32, weight: i32) {
+        let mut updated = false;
+        for (from, to) in [(node_a_id, node_b_id), (node_b_id, node_a_id)] {
+            if let Some(edges) = self.edges.get_mut(&from) {
+                for edge in edges.iter_mut().filter(|edge| edge.node_b_id == to) {
+                    updated |= edge.weight != weight;
+                    edge.weight = weight;
+                }
+            }
+        }
+
+        if updated {
+            self.invalidate();
+        }
+    }
+
+    /// 縮約階層のインデックスのバックグラウンドでの構築を開始できるかを確認する
+    ///
+    /// インデックスを使用するグラフでインデックスが破棄されており、構築中でもない場合のみ
+    /// 構築中として `true` を返す。構築は `rebuild_contraction_hierarchy` で行うこと
+    fn start_contraction_hierarchy_rebuild(&mut self) -> bool {
+        if !self.uses_contraction_hierarchy
+            || self.contraction_hierarchy.is_some()
+            || self.rebuilding_contraction_hierarchy
+        {
+            return false;
+        }
+        self.rebuilding_contraction_hierarchy = true;
+        true
+    }
+
+    /// ノードとエッジのみを複製したグラフを作成する
+    fn snapshot(&self) -> Graph {
+        let mut graph = Graph::new();
+        graph.nodes = self.nodes.clone();
+        graph.edges = self.edges.clone();
+        graph
+    }
+
+    /// グラフの変更に合わせて、計算済みのヒューリスティックと縮約階層のインデックスを破棄する
+    fn invalidate(&mut self) {
+        self.revision += 1;
+        self.heuristic_scale.take();
+        self.contraction_hierarchy = None;
+    }
+
+    /// 2つのノード間の最短距離を計算する
+    ///
+    /// 縮約階層のインデックスが構築済みの場合はインデックス上で、そうでない場合はダイクストラ法で計算する
+    ///
+    /// 戻り値: 最短距離（目的地ノードに到達できない場合は `i32::MAX`）
     pub fn shortest_path(&self, from_node_id: i32, to_node_id: i32) -> i32 {
+        let distance = match &self.contraction_hierarchy {
+            Some(contraction_hierarchy) => {
+                contraction_hierarchy.shortest_path(from_node_id, to_node_id)
+            }
+            None => self.dijkstra(from_node_id, &[to_node_id]).get(&to_node_id).cloned(),
+        };
+
+        // 目的地ノードに到達できない場合、i32::MAXを返す
+        distance.unwrap_or(i32::MAX)
+    }
+
+    /// 2つのノード間の最短経路を、通過するノードの順序とともにダイクストラ法で計算する
+    ///
+    /// 経路のコストは `shortest_path` と同じになる
+    ///
+    /// 戻り値: 最短経路（目的地ノードに到達できない場合は `None`）
+    pub fn shortest_route(&self, from_node_id: i32, to_node_id: i32) -> Option<Route> {
         let mut distances = HashMap::new();
+        let mut previous = HashMap::new();
         let mut heap = BinaryHeap::new();
 
-        #[derive(Copy, Clone, Eq, PartialEq)]
-        struct State {
-            cost: i32,
-            position: i32,
+        // 開始ノードの距離を初期化
+        distances.insert(from_node_id, 0);
+        heap.push(State {
+            cost: 0,
+            position: from_node_id,
+        });
+
+        while let Some(State { cost, position }) = heap.pop() {
+            // コストが記録されたコストより大きい場合、このノードをスキップ
+            if cost > *distances.get(&position).unwrap_or(&i32::MAX) {
+                continue;
+            }
+
+            // 目的地ノードに到達した場合、先行ノードを辿って経路を復元する
+            if position == to_node_id {
+                return Some(build_route(to_node_id, cost, &previous));
+            }
+
+            // 隣接ノードを探索
+            if let Some(edges) = self.edges.get(&position) {
+                for edge in edges {
+                    let next_cost = cost.saturating_add(edge.weight);
+
+                    // 隣接ノードへの短い経路が見つかった場合、距離を更新してヒープにプッシュ
+                    if next_cost < *distances.get(&edge.node_b_id).unwrap_or(&i32::MAX) {
+                        distances.insert(edge.node_b_id, next_cost);
+                        previous.insert(edge.node_b_id, (position, edge.weight));
+                        heap.push(State {
+                            cost: next_cost,
+                            position: edge.node_b_id,
+                        });
+                    }
+                }
+            }
         }
 
-        // 優先度キューは`Ord`に依存します。トレイトを明示的に実装して、キューを最小ヒープにします
-        impl Ord for State {
-            fn cmp(&self, other: &Self) -> Ordering {
-                // コストの順序を反転させることに注意してください。
-                // 同点の場合は位置を比較します - この手順は
-                // `PartialEq`と`Ord`の実装を一貫させるために必要です。
-                other.cost.cmp(&self.cost)
-                    .then_with(|| self.position.cmp(&other.position))
+        None
+    }
+
+    /// 2つのノード間の最短経路を、通過するノードの順序とともにA*探索で計算する
+    ///
+    /// ノードの座標間のユークリッド距離に、エリア内で最小の「単位長さあたりの重み」を掛けた値を
+    /// ヒューリスティックとして用いる。この値は実際の経路コストを超えないため、
+    /// ダイクストラ法と同じ最短コストを保ったまま探索するノード数を減らせる
+    ///
+    /// 戻り値: 最短経路（目的地ノードに到達できない場合は `None`）
+    pub fn shortest_route_astar(&self, from_node_id: i32, to_node_id: i32) -> Option<Route> {
+        let scale = self.heuristic_scale();
+        let target = self.nodes.get(&to_node_id);
+        let heuristic = |node_id: i32| match (target, self.nodes.get(&node_id)) {
+            (Some(target), Some(node)) => {
+                let dx = (target.x - node.x) as f64;
+                let dy = (target.y - node.y) as f64;
+                (dx.hypot(dy) * scale).floor() as i32
+            }
+            _ => 0,
+        };
+
+        let mut distances = HashMap::new();
+        let mut previous = HashMap::new();
+        let mut heap = BinaryHeap::new();
+
+        // 開始ノードの距離を初期化（ヒープには推定コストを積む）
+        distances.insert(from_node_id, 0);
+        heap.push(State { cost: heuristic(from_node_id), position: from_node_id });
+
+        while let Some(State { cost: estimate, position }) = heap.pop() {
+            let cost = *distances.get(&position).unwrap_or(&i32::MAX);
+
+            // 推定コストが記録されたコストから求めた推定値より大きい場合、このノードをスキップ
+            if estimate > cost.saturating_add(heuristic(position)) {
+                continue;
+            }
+
+            // 目的地ノードに到達した場合、先行ノードを辿って経路を復元する
+            if position == to_node_id {
+                return Some(build_route(to_node_id, cost, &previous));
+            }
+
+            // 隣接ノードを探索
+            if let Some(edges) = self.edges.get(&position) {
+                for edge in edges {
+                    let next_cost = cost.saturating_add(edge.weight);
+
+                    // 隣接ノードへの短い経路が見つかった場合、距離を更新してヒープにプッシュ
+                    if next_cost < *distances.get(&edge.node_b_id).unwrap_or(&i32::MAX) {
+                        distances.insert(edge.node_b_id, next_cost);
+                        previous.insert(edge.node_b_id, (position, edge.weight));
+                        heap.push(State {
+                            cost: next_cost.saturating_add(heuristic(edge.node_b_id)),
+                            position: edge.node_b_id,
+                        });
+                    }
+                }
             }
         }
 
-        // `PartialOrd`も実装する必要があります。
-        impl PartialOrd for State {
-            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                Some(self.cmp(other))
+        None
+    }
+
+    /// 1つの始点ノードから複数の目的地ノードまでの最短距離をまとめて計算する
+    ///
+    /// 縮約階層のインデックスを構築済みの場合はインデックスを用いて計算する
+    ///
+    /// 戻り値: 目的地ノードIDをキー、最短距離を値とするマップ（到達できないノードは含まれない）
+    pub fn shortest_paths(&self, from_node_id: i32, to_node_ids: &[i32]) -> HashMap<i32, i32> {
+        match &self.contraction_hierarchy {
+            Some(contraction_hierarchy) => {
+                contraction_hierarchy.shortest_paths(from_node_id, to_node_ids)
             }
+            None => self.dijkstra(from_node_id, to_node_ids),
         }
+    }
+
+    /// ダイクストラ法で1つの始点ノードから複数の目的地ノードまでの最短距離をまとめて計算する
+    ///
+    /// ダイクストラ法を1回だけ実行し、全ての目的地ノードの距離が確定した時点で探索を打ち切る
+    fn dijkstra(&self, from_node_id: i32, to_node_ids: &[i32]) -> HashMap<i32, i32> {
+        let mut remaining: HashSet<i32> = to_node_ids.iter().cloned().collect();
+        let mut results = HashMap::new();
+        if remaining.is_empty() {
+            return results;
+        }
+
+        let mut distances = HashMap::new();
+        let mut heap = BinaryHeap::new();
 
         // 開始ノードの距離を初期化
         distances.insert(from_node_id, 0);
         heap.push(State { cost: 0, position: from_node_id });
 
         while let Some(State { cost, position }) = heap.pop() {
-            // 目的地ノードに到達した場合、コストを返す
-            if position == to_node_id {
-                return cost;
-            }
-
             // コストが記録されたコストより大きい場合、このノードをスキップ
             if cost > *distances.get(&position).unwrap_or(&i32::MAX) {
                 continue;
             }
 
+            // 目的地ノードの距離が確定した場合、結果に記録する
+            if remaining.remove(&position) {
+                results.insert(position, cost);
+
+                // 全ての目的地ノードの距離が確定した場合、探索を終了
+                if remaining.is_empty() {
+                    break;
+                }
+            }
+
             // 隣接ノードを探索
             if let Some(edges) = self.edges.get(&position) {
                 for edge in edges {
-                    let next = State { cost: cost + edge.weight, position: edge.node_b_id };
+                    let next = State {
+                        cost: cost.saturating_add(edge.weight),
+                        position: edge.node_b_id,
+                    };
 
                     // 隣接ノードへの短い経路が見つかった場合、距離を更新してヒープにプッシュ
                     if next.cost < *distances.get(&next.position).unwrap_or(&i32::MAX) {
@@ -108,12 +345,428 @@ impl Graph {
             }
         }
 
-        // 目的地ノードに到達できない場合、i32::MAXを返す
-        distances.get(&to_node_id).cloned().unwrap_or(i32::MAX)
+        results
+    }
+
+    /// A*探索のヒューリスティックに用いる「単位長さあたりの重み」の最小値を取得する
+    ///
+    /// グラフが更新されるまでは計算結果を使い回す。
+    /// 座標が不明なノードを含むエッジがある場合は、許容的な値を保証できないため0を返す
+    fn heuristic_scale(&self) -> f64 {
+        *self.heuristic_scale.get_or_init(|| {
+            let mut min_weight_per_length = f64::INFINITY;
+            for edge in self.edges.values().flatten() {
+                let (Some(node_a), Some(node_b)) =
+                    (self.nodes.get(&edge.node_a_id), self.nodes.get(&edge.node_b_id))
+                else {
+                    return 0.0;
+                };
+                let length = ((node_a.x - node_b.x) as f64).hypot((node_a.y - node_b.y) as f64);
+                if length > 0.0 {
+                    min_weight_per_length = min_weight_per_length.min(edge.weight as f64 / length);
+                }
+            }
+
+            if min_weight_per_length.is_finite() {
+                min_weight_per_length
+            } else {
+                0.0
+            }
+        })
     }
 }
 
-/// グラフ上の2つのノード間の最短距離を計算する
-fn calculate_distance(graph: &Graph, node_id_1: i32, node_id_2: i32) -> i32 {
-    graph.shortest_path(node_id_1, node_id_2)
+/// 目的地ノードから先行ノードを辿って経路を復元する
+fn build_route(to_node_id: i32, cost: i32, previous: &HashMap<i32, (i32, i32)>) -> Route {
+    let mut node_ids = vec![to_node_id];
+    let mut weights = Vec::new();
+    let mut position = to_node_id;
+    while let Some(&(prev_node_id, weight)) = previous.get(&position) {
+        node_ids.push(prev_node_id);
+        weights.push(weight);
+        position = prev_node_id;
+    }
+    node_ids.reverse();
+    weights.reverse();
+
+    Route {
+        node_ids,
+        weights,
+        cost,
+    }
+}
+
+/// グラフのロックを保持せずに縮約階層のインデックスを構築し、構築後に差し替える
+///
+/// 構築後の最短距離の計算はインデックス上の双方向探索で行われる。
+/// 構築はグラフの複製に対して行うため、構築中も最短距離の計算やエッジの更新を妨げない。
+/// 構築中にグラフが更新された場合は、構築したインデックスを破棄して最新のグラフで構築し直す
+pub fn rebuild_contraction_hierarchy(graph: &RwLock<Graph>) {
+    loop {
+        let (revision, snapshot) = {
+            let graph = graph.read().unwrap_or_else(PoisonError::into_inner);
+            (graph.revision, graph.snapshot())
+        };
+        let contraction_hierarchy = ContractionHierarchy::build(&snapshot);
+
+        let mut graph = graph.write().unwrap_or_else(PoisonError::into_inner);
+        if graph.revision == revision {
+            graph.uses_contraction_hierarchy = true;
+            graph.contraction_hierarchy = Some(contraction_hierarchy);
+            graph.rebuilding_contraction_hierarchy = false;
+            return;
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct State {
+    cost: i32,
+    position: i32,
+}
+
+// 優先度キューは`Ord`に依存します。トレイトを明示的に実装して、キューを最小ヒープにします
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // コストの順序を反転させることに注意してください。
+        // 同点の場合は位置を比較します - この手順は
+        // `PartialEq`と`Ord`の実装を一貫させるために必要です。
+        other.cost.cmp(&self.cost)
+            .then_with(|| self.position.cmp(&other.position))
+    }
+}
+
+// `PartialOrd`も実装する必要があります。
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// エリアごとのグラフをメモリ上に保持するキャッシュ
+///
+/// 全ワーカーで共有され、エッジの重みが更新された場合はキャッシュ上のグラフも直接更新される
+///
+/// `contraction_hierarchy_min_nodes` - 縮約階層を構築するエリアのノード数の下限（`None` の場合は構築しない）
+#[derive(Debug)]
+pub struct GraphCache {
+    areas: RwLock<CachedAreas>,
+    contraction_hierarchy_min_nodes: Option<usize>,
+}
+
+/// キャッシュ済みのグラフとエリアごとの世代
+///
+/// 世代はエリアのエッジが更新されるたびに進み、読み込み中に更新されたグラフを登録しないために用いる
+#[derive(Debug, Default)]
+struct CachedAreas {
+    graphs: HashMap<i32, Arc<RwLock<Graph>>>,
+    generations: HashMap<i32, u64>,
+}
+
+impl GraphCache {
+    pub fn new(contraction_hierarchy_min_nodes: Option<usize>) -> Self {
+        GraphCache {
+            areas: RwLock::new(CachedAreas::default()),
+            contraction_hierarchy_min_nodes,
+        }
+    }
+
+    /// キャッシュ済みのエリアのグラフを取得する
+    pub fn get(&self, area_id: i32) -> Option<Arc<RwLock<Graph>>> {
+        self.areas
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .graphs
+            .get(&area_id)
+            .cloned()
+    }
+
+    /// エリアの現在の世代を取得する
+    ///
+    /// グラフをデータベースから読み込む前に取得し、`insert` に渡すこと
+    pub fn generation(&self, area_id: i32) -> u64 {
+        self.areas
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .generations
+            .get(&area_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// エリアのグラフをキャッシュに登録する
+    ///
+    /// `generation` - グラフを読み込む前に `generation` で取得したエリアの世代
+    ///
+    /// 他のリクエストが先に登録していた場合は、既存のグラフを返す。
+    /// 読み込み中にエッジが更新されて世代が進んでいた場合は、古い重みを含む可能性があるため登録せずに返す。
+    /// ノード数が下限以上のエリアは、登録後にバックグラウンドで縮約階層のインデックスを構築する
+    pub fn insert(&self, area_id: i32, generation: u64, mut graph: Graph) -> Arc<RwLock<Graph>> {
+        let mut areas = self.areas.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(cached) = areas.graphs.get(&area_id) {
+            return cached.clone();
+        }
+
+        graph.uses_contraction_hierarchy = self
+            .contraction_hierarchy_min_nodes
+            .is_some_and(|min_nodes| graph.nodes.len() >= min_nodes);
+        let graph = Arc::new(RwLock::new(graph));
+        if areas.generations.get(&area_id).cloned().unwrap_or_default() == generation {
+            areas.graphs.insert(area_id, graph.clone());
+            spawn_contraction_hierarchy_rebuild(&graph);
+        }
+        graph
+    }
+
+    /// キャッシュ済みのグラフのエッジの重みを更新する
+    ///
+    /// エリアの世代を進めるため、更新前に読み込みを始めたグラフはキャッシュに登録されない。
+    /// エリアのグラフがまだ読み込まれていない場合は、グラフの更新は行わない
+    pub fn update_edge_weight(&self, area_id: i32, node_a_id: i32, node_b_id: i32, weight: i32) {
+        let graph = {
+            let mut areas = self.areas.write().unwrap_or_else(PoisonError::into_inner);
+            *areas.generations.entry(area_id).or_default() += 1;
+            areas.graphs.get(&area_id).cloned()
+        };
+
+        if let Some(graph) = graph {
+            graph
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .update_edge_weight(node_a_id, node_b_id, weight);
+            spawn_contraction_hierarchy_rebuild(&graph);
+        }
+    }
+}
+
+/// 縮約階層のインデックスが必要で、構築中でない場合にバックグラウンドでの構築を開始する
+///
+/// 構築が終わるまでの最短距離の計算はダイクストラ法で行われる
+fn spawn_contraction_hierarchy_rebuild(graph: &Arc<RwLock<Graph>>) {
+    let should_rebuild = graph
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .start_contraction_hierarchy_rebuild();
+    if should_rebuild {
+        let graph = graph.clone();
+        thread::spawn(move || rebuild_contraction_hierarchy(&graph));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// 初期データのCSVからエリアごとのグラフを構築する
+    fn load_seeded_graphs() -> HashMap<i32, Graph> {
+        let csv_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../mysql/init/csv");
+        let nodes_csv = std::fs::read_to_string(format!("{}/nodes.csv", csv_dir)).unwrap();
+        let edges_csv = std::fs::read_to_string(format!("{}/edges.csv", csv_dir)).unwrap();
+
+        // nodes.csv の行番号がそのままノードIDになる
+        let mut graphs: HashMap<i32, Graph> = HashMap::new();
+        let mut area_by_node_id = HashMap::new();
+        for (index, line) in nodes_csv.lines().skip(1).enumerate() {
+            let columns: Vec<&str> = line.split(',').collect();
+            let id = index as i32 + 1;
+            let area_id: i32 = columns[1].parse().unwrap();
+            area_by_node_id.insert(id, area_id);
+            graphs.entry(area_id).or_insert_with(Graph::new).add_node(Node {
+                id,
+                x: columns[2].parse().unwrap(),
+                y: columns[3].parse().unwrap(),
+            });
+        }
+
+        for line in edges_csv.lines().skip(1) {
+            let columns: Vec<i32> = line.split(',').map(|c| c.parse().unwrap()).collect();
+            let area_id = area_by_node_id[&columns[0]];
+            graphs.get_mut(&area_id).unwrap().add_edge(Edge {
+                node_a_id: columns[0],
+                node_b_id: columns[1],
+                weight: columns[2],
+            });
+        }
+
+        graphs
+    }
+
+    #[test]
+    fn route_cost_matches_shortest_path_on_seeded_maps() {
+        let graphs = load_seeded_graphs();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for (area_id, graph) in &graphs {
+            let mut node_ids: Vec<i32> = graph.nodes.keys().cloned().collect();
+            node_ids.sort();
+
+            for _ in 0..30 {
+                let from = node_ids[rng.gen_range(0..node_ids.len())];
+                let to = node_ids[rng.gen_range(0..node_ids.len())];
+
+                let expected = match graph.shortest_path(from, to) {
+                    i32::MAX => None,
+                    cost => Some(cost),
+                };
+                let route = graph.shortest_route(from, to);
+                assert_eq!(
+                    route.as_ref().map(|route| route.cost),
+                    expected,
+                    "area {} from {} to {}",
+                    area_id,
+                    from,
+                    to
+                );
+
+                if let Some(route) = route {
+                    assert_eq!(route.node_ids.first(), Some(&from));
+                    assert_eq!(route.node_ids.last(), Some(&to));
+                    assert_eq!(route.weights.len() + 1, route.node_ids.len());
+                    assert_eq!(route.weights.iter().sum::<i32>(), route.cost);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn astar_matches_dijkstra_on_seeded_maps() {
+        let graphs = load_seeded_graphs();
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for (area_id, graph) in &graphs {
+            let mut node_ids: Vec<i32> = graph.nodes.keys().cloned().collect();
+            node_ids.sort();
+
+            for _ in 0..30 {
+                let from = node_ids[rng.gen_range(0..node_ids.len())];
+                let to = node_ids[rng.gen_range(0..node_ids.len())];
+
+                let expected = graph.shortest_paths(from, &[to]).get(&to).cloned();
+                let route = graph.shortest_route_astar(from, to);
+                assert_eq!(
+                    route.as_ref().map(|route| route.cost),
+                    expected,
+                    "area {} from {} to {}",
+                    area_id,
+                    from,
+                    to
+                );
+
+                if let Some(route) = route {
+                    assert_eq!(route.node_ids.first(), Some(&from));
+                    assert_eq!(route.node_ids.last(), Some(&to));
+                    assert_eq!(route.weights.len() + 1, route.node_ids.len());
+                    assert_eq!(route.weights.iter().sum::<i32>(), route.cost);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn astar_matches_dijkstra_after_edge_update() {
+        let mut graphs = load_seeded_graphs();
+        let graph = graphs.get_mut(&1).unwrap();
+
+        let mut node_ids: Vec<i32> = graph.nodes.keys().cloned().collect();
+        node_ids.sort();
+        let (from, to) = (node_ids[0], node_ids[node_ids.len() - 1]);
+
+        // 最短経路上のエッジを軽くしてもA*の結果が最短コストと一致することを確認する
+        let route = graph.shortest_route_astar(from, to).unwrap();
+        graph.update_edge_weight(route.node_ids[0], route.node_ids[1], 0);
+
+        let expected = graph.shortest_paths(from, &[to]).get(&to).cloned();
+        assert_eq!(graph.shortest_route_astar(from, to).map(|route| route.cost), expected);
+    }
+
+    fn two_node_graph(weight: i32) -> Graph {
+        let mut graph = Graph::new();
+        graph.add_node(Node { id: 1, x: 0, y: 0 });
+        graph.add_node(Node { id: 2, x: 10, y: 0 });
+        graph.add_edge(Edge {
+            node_a_id: 1,
+            node_b_id: 2,
+            weight,
+        });
+        graph
+    }
+
+    #[test]
+    fn cache_does_not_keep_graph_loaded_before_edge_update() {
+        let cache = GraphCache::new(None);
+
+        // グラフの読み込み中に、まだキャッシュされていないエリアのエッジが更新された場合
+        let generation = cache.generation(1);
+        let stale_graph = two_node_graph(5);
+        cache.update_edge_weight(1, 1, 2, 7);
+
+        // 読み込んだグラフはそのリクエストでは使えるが、キャッシュには登録されない
+        let graph = cache.insert(1, generation, stale_graph);
+        assert_eq!(graph.read().unwrap().shortest_path(1, 2), 5);
+        assert!(cache.get(1).is_none());
+
+        // 更新後に読み込み直したグラフは登録され、以降の更新も反映される
+        let graph = cache.insert(1, cache.generation(1), two_node_graph(7));
+        assert!(Arc::ptr_eq(&graph, &cache.get(1).unwrap()));
+        cache.update_edge_weight(1, 1, 2, 9);
+        assert_eq!(cache.get(1).unwrap().read().unwrap().shortest_path(1, 2), 9);
+    }
+
+    #[test]
+    fn cache_returns_graph_registered_first() {
+        let cache = GraphCache::new(None);
+        let generation = cache.generation(1);
+
+        let first = cache.insert(1, generation, two_node_graph(5));
+        let second = cache.insert(1, generation, two_node_graph(7));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(second.read().unwrap().shortest_path(1, 2), 5);
+    }
+
+    #[test]
+    fn cache_builds_contraction_hierarchy_in_background() {
+        let cache = GraphCache::new(Some(2));
+        let graph = cache.insert(1, cache.generation(1), two_node_graph(5));
+
+        // 構築が終わるまではダイクストラ法で計算し、構築後はインデックスで計算する
+        assert_eq!(graph.read().unwrap().shortest_path(1, 2), 5);
+        wait_for_contraction_hierarchy(&graph);
+        assert_eq!(graph.read().unwrap().shortest_path(1, 2), 5);
+
+        // 重みの更新でインデックスは破棄されるが、更新後の重みで計算され、インデックスも再構築される
+        cache.update_edge_weight(1, 1, 2, 3);
+        assert_eq!(graph.read().unwrap().shortest_path(1, 2), 3);
+        wait_for_contraction_hierarchy(&graph);
+        assert_eq!(graph.read().unwrap().shortest_path(1, 2), 3);
+    }
+
+    #[test]
+    fn saturates_costs_instead_of_overflowing() {
+        let mut graph = Graph::new();
+        for id in 1..=3 {
+            graph.add_node(Node { id, x: 0, y: 0 });
+        }
+        for (node_a_id, node_b_id) in [(1, 2), (2, 3)] {
+            graph.add_edge(Edge {
+                node_a_id,
+                node_b_id,
+                weight: i32::MAX - 1,
+            });
+        }
+
+        assert_eq!(graph.shortest_path(1, 3), i32::MAX);
+        assert!(graph.shortest_route_astar(1, 3).is_none());
+    }
+
+    fn wait_for_contraction_hierarchy(graph: &RwLock<Graph>) {
+        for _ in 0..500 {
+            if graph.read().unwrap().contraction_hierarchy.is_some() {
+                return;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("contraction hierarchy was not built");
+    }
 }
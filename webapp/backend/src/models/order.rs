@@ -1,6 +1,11 @@
+use std::fmt;
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
 use sqlx::FromRow;
 
+use crate::errors::AppError;
+
 #[derive(FromRow, Clone, Debug)]
 pub struct Order {
     pub id: i32,
@@ -23,3 +28,195 @@ pub struct CompletedOrder {
     pub completed_time: DateTime<Utc>,
     pub car_value: f64,
 }
+
+/// 注文のステータスを表す列挙型
+///
+/// 注文は以下の順に遷移し、`completed` と `cancelled` 以外のステータスからは `cancelled` に遷移できる
+/// `pending` → `dispatched` → `en_route` → `arrived` → `towing` → `completed`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderStatus {
+    Pending,
+    Dispatched,
+    EnRoute,
+    Arrived,
+    Towing,
+    Completed,
+    Cancelled,
+}
+
+impl OrderStatus {
+    /// データベースに保存する文字列表現を返す
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Dispatched => "dispatched",
+            OrderStatus::EnRoute => "en_route",
+            OrderStatus::Arrived => "arrived",
+            OrderStatus::Towing => "towing",
+            OrderStatus::Completed => "completed",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// 指定したステータスへ遷移できるかどうかを返す
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        matches!(
+            (self, next),
+            (OrderStatus::Pending, OrderStatus::Dispatched)
+                | (OrderStatus::Dispatched, OrderStatus::EnRoute)
+                | (OrderStatus::EnRoute, OrderStatus::Arrived)
+                | (OrderStatus::Arrived, OrderStatus::Towing)
+                | (OrderStatus::Towing, OrderStatus::Completed)
+                | (
+                    OrderStatus::Pending
+                        | OrderStatus::Dispatched
+                        | OrderStatus::EnRoute
+                        | OrderStatus::Arrived
+                        | OrderStatus::Towing,
+                    OrderStatus::Cancelled,
+                )
+        )
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(OrderStatus::Pending),
+            "dispatched" => Ok(OrderStatus::Dispatched),
+            "en_route" => Ok(OrderStatus::EnRoute),
+            "arrived" => Ok(OrderStatus::Arrived),
+            "towing" => Ok(OrderStatus::Towing),
+            "completed" => Ok(OrderStatus::Completed),
+            "cancelled" => Ok(OrderStatus::Cancelled),
+            _ => Err(AppError::InvalidOrderStatus(s.to_string())),
+        }
+    }
+}
+
+/// 注文のキャンセル理由を表す列挙型
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelReason {
+    /// クライアントの都合によるキャンセル
+    ClientRequest,
+    /// 重複した注文
+    DuplicateOrder,
+    /// 割り当て可能なレッカー車がない
+    NoTowTruckAvailable,
+    /// 注文の場所にレッカー車が到達できない
+    UnreachableLocation,
+    /// その他
+    Other,
+}
+
+impl CancelReason {
+    /// データベースに保存する文字列表現を返す
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CancelReason::ClientRequest => "client_request",
+            CancelReason::DuplicateOrder => "duplicate_order",
+            CancelReason::NoTowTruckAvailable => "no_tow_truck_available",
+            CancelReason::UnreachableLocation => "unreachable_location",
+            CancelReason::Other => "other",
+        }
+    }
+}
+
+impl FromStr for CancelReason {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client_request" => Ok(CancelReason::ClientRequest),
+            "duplicate_order" => Ok(CancelReason::DuplicateOrder),
+            "no_tow_truck_available" => Ok(CancelReason::NoTowTruckAvailable),
+            "unreachable_location" => Ok(CancelReason::UnreachableLocation),
+            "other" => Ok(CancelReason::Other),
+            _ => Err(AppError::InvalidCancelReason(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_STATUSES: [OrderStatus; 7] = [
+        OrderStatus::Pending,
+        OrderStatus::Dispatched,
+        OrderStatus::EnRoute,
+        OrderStatus::Arrived,
+        OrderStatus::Towing,
+        OrderStatus::Completed,
+        OrderStatus::Cancelled,
+    ];
+
+    #[test]
+    fn allows_only_listed_transitions() {
+        use OrderStatus::*;
+        let allowed = [
+            (Pending, Dispatched),
+            (Dispatched, EnRoute),
+            (EnRoute, Arrived),
+            (Arrived, Towing),
+            (Towing, Completed),
+            (Pending, Cancelled),
+            (Dispatched, Cancelled),
+            (EnRoute, Cancelled),
+            (Arrived, Cancelled),
+            (Towing, Cancelled),
+        ];
+
+        for from in ALL_STATUSES {
+            for to in ALL_STATUSES {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from, to)),
+                    "{} -> {}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_skipping_reverting_and_leaving_terminal_statuses() {
+        use OrderStatus::*;
+        let rejected = [
+            (Pending, EnRoute),
+            (Dispatched, Towing),
+            (Pending, Completed),
+            (Towing, Arrived),
+            (Dispatched, Pending),
+            (Pending, Pending),
+            (Completed, Cancelled),
+            (Completed, Pending),
+            (Cancelled, Pending),
+            (Cancelled, Cancelled),
+        ];
+
+        for (from, to) in rejected {
+            assert!(!from.can_transition_to(to), "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn parses_its_own_string_representation() {
+        for status in ALL_STATUSES {
+            assert_eq!(status.as_str().parse::<OrderStatus>().unwrap(), status);
+        }
+        assert!(matches!(
+            "unknown".parse::<OrderStatus>(),
+            Err(AppError::InvalidOrderStatus(_))
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Duration, Utc};
 use sqlx::FromRow;
 
 /// レッカー車を表す構造体
@@ -9,4 +10,73 @@ pub struct TowTruck {
     pub status: String,
     pub area_id: i32,
     pub node_id: i32,
-}
\ No newline at end of file
+    /// 現在位置を記録した時刻
+    pub location_updated_at: DateTime<Utc>,
+}
+
+/// レッカー車の位置の履歴を表す構造体
+///
+/// `x`, `y` はノードの座標
+/// `implausible` - 前回の位置から経過時間内に到達できない移動として記録されたかどうか
+#[derive(FromRow, Clone, Debug)]
+pub struct LocationRecord {
+    pub node_id: i32,
+    pub x: i32,
+    pub y: i32,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub implausible: bool,
+}
+
+/// 位置の更新が現実的な移動かどうかを判定する設定を表す構造体
+///
+/// `max_speed` - レッカー車が1秒あたりに進めるグラフ上の距離の上限
+/// `grace_distance` - 経過時間に関わらず許容するグラフ上の距離（位置の記録の誤差を吸収する）
+#[derive(Clone, Copy, Debug)]
+pub struct LocationPlausibilityPolicy {
+    pub max_speed: f64,
+    pub grace_distance: i32,
+}
+
+impl LocationPlausibilityPolicy {
+    /// 経過時間内にグラフ上の距離を移動できるかどうかを返す
+    ///
+    /// `distance` - 前回の位置からのグラフ上の最短距離（到達できない場合は `None`）
+    /// `elapsed` - 前回の位置の記録からの経過時間
+    pub fn is_plausible(&self, distance: Option<i32>, elapsed: Duration) -> bool {
+        let Some(distance) = distance else {
+            return false;
+        };
+        let elapsed_seconds = elapsed.num_milliseconds().max(0) as f64 / 1000.0;
+        distance as f64 <= self.max_speed * elapsed_seconds + self.grace_distance as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> LocationPlausibilityPolicy {
+        LocationPlausibilityPolicy {
+            max_speed: 2.0,
+            grace_distance: 5,
+        }
+    }
+
+    #[test]
+    fn allows_moves_within_max_speed() {
+        assert!(policy().is_plausible(Some(25), Duration::seconds(10)));
+        assert!(!policy().is_plausible(Some(26), Duration::seconds(10)));
+    }
+
+    #[test]
+    fn allows_grace_distance_without_elapsed_time() {
+        assert!(policy().is_plausible(Some(0), Duration::zero()));
+        assert!(policy().is_plausible(Some(5), Duration::seconds(-3)));
+        assert!(!policy().is_plausible(Some(6), Duration::zero()));
+    }
+
+    #[test]
+    fn rejects_unreachable_moves() {
+        assert!(!policy().is_plausible(None, Duration::days(1)));
+    }
+}
@@ -1,15 +1,69 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, Utc};
 use sqlx::FromRow;
 
+use crate::errors::AppError;
+
 /// ユーザーを表す構造体
 #[derive(FromRow, Clone, Debug)]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub password: String,
-    pub profile_image: String,
     pub role: String,
 }
 
+impl User {
+    /// ユーザーが指定したロールかどうかを返す
+    pub fn has_role(&self, role: Role) -> bool {
+        self.role == role.as_str()
+    }
+}
+
+/// ユーザーのロールを表す列挙型
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Client,
+    Dispatcher,
+    Driver,
+    /// 管理者（ユーザー登録では作成できない）
+    Admin,
+}
+
+impl Role {
+    /// データベースに保存する文字列表現を返す
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Client => "client",
+            Role::Dispatcher => "dispatcher",
+            Role::Driver => "driver",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(Role::Client),
+            "dispatcher" => Ok(Role::Dispatcher),
+            "driver" => Ok(Role::Driver),
+            "admin" => Ok(Role::Admin),
+            _ => Err(AppError::InvalidRole(s.to_string())),
+        }
+    }
+}
+
 /// セッションを表す構造体
 #[derive(FromRow, Clone, Debug)]
 pub struct Session {
@@ -17,15 +71,25 @@ pub struct Session {
     pub user_id: i32,
     pub session_token: String,
     pub is_valid: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
 }
 
-/// ドライバーを表す構造体
-#[derive(FromRow, Clone, Debug)]
-pub struct Driver {
-    pub id: i32,
-    pub user_id: i32,
-    pub session_token: String,
-    pub is_valid: bool,
+/// セッションの有効期限の設定を表す構造体
+///
+/// `lifetime` - 作成からの有効期間（トークンを更新しても延長されない）
+/// `idle_timeout` - 最後の利用からの有効期間
+#[derive(Clone, Copy, Debug)]
+pub struct SessionPolicy {
+    pub lifetime: Duration,
+    pub idle_timeout: Duration,
+}
+
+impl SessionPolicy {
+    /// セッションが期限切れかどうかを返す
+    pub fn is_expired(&self, session: &Session, now: DateTime<Utc>) -> bool {
+        now - session.created_at >= self.lifetime || now - session.last_seen_at >= self.idle_timeout
+    }
 }
 
 /// ディスパッチャーを表す構造体
@@ -34,4 +98,64 @@ pub struct Dispatcher {
     pub id: i32,
     pub user_id: i32,
     pub area_id: i32,
-}
\ No newline at end of file
+}
+
+/// 認証済みのユーザーを表す構造体
+///
+/// 認証ミドルウェアがセッションから解決し、リクエストの extensions に格納する
+#[derive(Clone, Debug)]
+pub struct AuthenticatedUser {
+    pub user: User,
+    /// リクエストの認証に使用したセッション
+    pub session: Session,
+    /// ユーザーがディスパッチャーの場合のディスパッチャー情報
+    pub dispatcher: Option<Dispatcher>,
+}
+
+impl AuthenticatedUser {
+    /// ディスパッチャーの担当エリアIDを返す
+    pub fn area_id(&self) -> Option<i32> {
+        self.dispatcher.as_ref().map(|dispatcher| dispatcher.area_id)
+    }
+
+    /// 注文を作成するクライアントのユーザーIDを返す
+    ///
+    /// `requested_client_id` - リクエストで指定されたクライアントID
+    ///
+    /// 認証済みのユーザー以外のIDが指定された場合は `AppError::Forbidden` を返す
+    pub fn client_id(&self, requested_client_id: Option<i32>) -> Result<i32, AppError> {
+        match_requested_id(self.user.id, requested_client_id)
+    }
+
+    /// ディスパッチャーIDを返す
+    ///
+    /// `requested_dispatcher_id` - リクエストで指定されたディスパッチャーID
+    ///
+    /// 認証済みのディスパッチャー以外のIDが指定された場合や
+    /// ディスパッチャーでない場合は `AppError::Forbidden` を返す
+    pub fn dispatcher_id(&self, requested_dispatcher_id: Option<i32>) -> Result<i32, AppError> {
+        let dispatcher = self.dispatcher.as_ref().ok_or(AppError::Forbidden)?;
+        match_requested_id(dispatcher.id, requested_dispatcher_id)
+    }
+
+    /// ディスパッチャーが参照できるエリアIDを返す
+    ///
+    /// `requested_area_id` - リクエストで指定されたエリアID
+    ///
+    /// 指定がない場合は担当エリアを返し、担当エリア以外が指定された場合や
+    /// ディスパッチャーでない場合は `AppError::Forbidden` を返す
+    pub fn scoped_area_id(&self, requested_area_id: Option<i32>) -> Result<i32, AppError> {
+        let area_id = self.area_id().ok_or(AppError::Forbidden)?;
+        match_requested_id(area_id, requested_area_id)
+    }
+}
+
+/// リクエストで指定されたIDがセッションから得たIDと一致するかを確認する
+///
+/// 指定がない場合はセッションから得たIDを返す
+fn match_requested_id(session_id: i32, requested_id: Option<i32>) -> Result<i32, AppError> {
+    match requested_id {
+        Some(requested_id) if requested_id != session_id => Err(AppError::Forbidden),
+        _ => Ok(session_id),
+    }
+}
@@ -1,6 +1,8 @@
 use crate::errors::AppError;
-use crate::models::user::{Dispatcher, User};
+use crate::models::user::{Dispatcher, Role, User};
+use crate::utils::verify_password;
 use crate::{domains::auth_service::AuthRepository, models::user::Session};
+use chrono::{DateTime, Utc};
 use sqlx::mysql::MySqlPool;
 
 /// 認証リポジトリの実装構造体
@@ -69,53 +71,123 @@ impl AuthRepository for AuthRepositoryImpl {
     /// `username` - ユーザー名
     /// `password` - パスワード
     ///
-    /// 成功した場合は `User` を返し、失敗した場合は `AppError` を返す
+    /// 保存されたハッシュでパスワードを検証し、成功した場合は `User` を返す。
+    /// ユーザーが存在しない場合やパスワードが一致しない場合は `AppError::Unauthorized` を返す
     async fn authenticate_user(&self, username: &str, password: &str) -> Result<User, AppError> {
-        let user =
-            sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ? AND password = ?")
-                .bind(username)
-                .bind(password)
-                .fetch_one(&self.pool)
-                .await?;
-
-        Ok(user)
+        let user = self
+            .find_user_by_username(username)
+            .await?
+            .ok_or(AppError::Unauthorized)?;
+        match verify_password(&user.password, password)? {
+            true => Ok(user),
+            false => Err(AppError::Unauthorized),
+        }
     }
 
-    /// 新しいユーザーを作成する
+    /// 新しいユーザーを作成し、そのユーザーのセッションを作成する
     ///
     /// `username` - ユーザー名
-    /// `password` - パスワード
-    /// `role` - ユーザーの役割
+    /// `password` - ハッシュ化したパスワード
+    /// `role` - ユーザーのロール
+    /// `area_id` - ディスパッチャーの担当エリアID（指定した場合はディスパッチャーも作成する）
+    /// `session_token` - セッショントークン
+    /// `now` - セッションの作成時刻
     ///
-    /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
+    /// 全ての作成を1つのトランザクションで行う。
+    /// エリアが存在しない場合は `AppError::InvalidArea` を返し、ユーザー名が既に使われている場合は
+    /// 一意制約の違反（409 として返される）となる
     async fn create_user(
         &self,
         username: &str,
         password: &str,
-        role: &str,
-    ) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO users (username, password, role) VALUES (?, ?, ?)")
+        role: Role,
+        area_id: Option<i32>,
+        session_token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(User, Option<Dispatcher>), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(area_id) = area_id {
+            let area: Option<i32> =
+                sqlx::query_scalar("SELECT id FROM areas WHERE id = ? LOCK IN SHARE MODE")
+                    .bind(area_id)
+                    .fetch_optional(&mut tx)
+                    .await?;
+            if area.is_none() {
+                return Err(AppError::InvalidArea(area_id));
+            }
+        }
+
+        let user_id = sqlx::query("INSERT INTO users (username, password, role) VALUES (?, ?, ?)")
             .bind(username)
             .bind(password)
-            .bind(role)
-            .execute(&self.pool)
+            .bind(role.as_str())
+            .execute(&mut tx)
+            .await?
+            .last_insert_id() as i32;
+
+        // 有効期限はアプリケーションの時刻と比較するため、DB のタイムゾーンに依存しないよう時刻を指定する
+        sqlx::query(
+            "INSERT INTO sessions (user_id, session_token, created_at, last_seen_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(session_token)
+        .bind(now)
+        .bind(now)
+        .execute(&mut tx)
             .await?;
 
-        Ok(())
+        let dispatcher = match area_id {
+            Some(area_id) => {
+                let dispatcher_id =
+                    sqlx::query("INSERT INTO dispatchers (user_id, area_id) VALUES (?, ?)")
+                        .bind(user_id)
+                        .bind(area_id)
+                        .execute(&mut tx)
+                        .await?
+                        .last_insert_id() as i32;
+                Some(Dispatcher {
+                    id: dispatcher_id,
+                    user_id,
+                    area_id,
+                })
+            }
+            None => None,
+        };
+
+        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok((user, dispatcher))
     }
 
     /// 新しいセッションを作成する
     ///
     /// `user_id` - ユーザーID
     /// `session_token` - セッショントークン
+    /// `now` - セッションの作成時刻
     ///
     /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
-    async fn create_session(&self, user_id: i32, session_token: &str) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO sessions (user_id, session_token) VALUES (?, ?)")
-            .bind(user_id)
-            .bind(session_token)
-            .execute(&self.pool)
-            .await?;
+    async fn create_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        // 有効期限はアプリケーションの時刻と比較するため、DB のタイムゾーンに依存しないよう時刻を指定する
+        sqlx::query(
+            "INSERT INTO sessions (user_id, session_token, created_at, last_seen_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(session_token)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
 
         Ok(())
     }
@@ -152,6 +224,246 @@ impl AuthRepository for AuthRepositoryImpl {
         Ok(session)
     }
 
+    /// セッションの最終利用時刻を更新する
+    ///
+    /// `session_id` - セッションID
+    /// `last_seen_at` - 最終利用時刻
+    ///
+    /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
+    async fn touch_session(
+        &self,
+        session_id: i32,
+        last_seen_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query("UPDATE sessions SET last_seen_at = ? WHERE id = ?")
+            .bind(last_seen_at)
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// セッショントークンを新しいトークンに置き換え、最終利用時刻を更新する
+    ///
+    /// 作成時刻は変更しないため、セッションの有効期間は延長されない
+    ///
+    /// `session_token` - 現在のセッショントークン
+    /// `new_session_token` - 新しいセッショントークン
+    /// `now` - 現在時刻
+    ///
+    /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
+    /// - 有効なセッションが存在しない場合は `AppError::Unauthorized`
+    async fn rotate_session(
+        &self,
+        session_token: &str,
+        new_session_token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let result = sqlx::query(
+            "UPDATE sessions SET session_token = ?, last_seen_at = ? WHERE session_token = ? AND is_valid = TRUE",
+        )
+        .bind(new_session_token)
+        .bind(now)
+        .bind(session_token)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::Unauthorized);
+        }
+
+        Ok(())
+    }
+
+    /// 期限切れのセッションを削除する
+    ///
+    /// `created_before` - この時刻より前に作成されたセッションを削除する
+    /// `last_seen_before` - この時刻より前に最後に利用されたセッションを削除する
+    ///
+    /// 成功した場合は削除したセッションの数を返し、失敗した場合は `AppError` を返す
+    async fn delete_expired_sessions(
+        &self,
+        created_before: DateTime<Utc>,
+        last_seen_before: DateTime<Utc>,
+    ) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM sessions WHERE created_at < ? OR last_seen_at < ?")
+            .bind(created_before)
+            .bind(last_seen_before)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// ユーザーIDでセッションを検索する
+    ///
+    /// `user_id` - ユーザーID
+    ///
+    /// 成功した場合は作成日時の新しい順の `Vec<Session>` を返し、失敗した場合は `AppError` を返す
+    async fn find_sessions_by_user_id(&self, user_id: i32) -> Result<Vec<Session>, AppError> {
+        let sessions = sqlx::query_as::<_, Session>(
+            "SELECT * FROM sessions WHERE user_id = ? ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// ユーザーのセッションをIDで削除する
+    ///
+    /// `user_id` - ユーザーID
+    /// `session_id` - セッションID
+    ///
+    /// 成功した場合は削除したセッションの数を返し、失敗した場合は `AppError` を返す
+    async fn delete_session_by_id(&self, user_id: i32, session_id: i32) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM sessions WHERE id = ? AND user_id = ?")
+            .bind(session_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// ユーザーの全てのセッションを削除する
+    ///
+    /// `user_id` - ユーザーID
+    ///
+    /// 成功した場合は削除したセッションの数を返し、失敗した場合は `AppError` を返す
+    async fn delete_sessions_by_user_id(&self, user_id: i32) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM sessions WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// ユーザーのパスワードを更新し、指定したセッション以外のセッションを削除する
+    ///
+    /// `user_id` - ユーザーID
+    /// `password` - ハッシュ化されたパスワード
+    /// `keep_session_id` - 削除しないセッションのID
+    ///
+    /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
+    async fn update_password(
+        &self,
+        user_id: i32,
+        password: &str,
+        keep_session_id: i32,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+            .bind(password)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query("DELETE FROM sessions WHERE user_id = ? AND id <> ?")
+            .bind(user_id)
+            .bind(keep_session_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// パスワードリセット用のトークンを作成し、ユーザーの未使用のトークンと全てのセッションを削除する
+    ///
+    /// `user_id` - ユーザーID
+    /// `token_hash` - パスワードリセット用のトークンのハッシュ値
+    /// `expires_at` - 有効期限
+    ///
+    /// 全ての変更を1つのトランザクションで行う。
+    /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
+    async fn create_password_reset_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = ? AND used_at IS NULL")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES (?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query("DELETE FROM sessions WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// パスワードリセット用のトークンを使用してパスワードを再設定する
+    ///
+    /// `token_hash` - パスワードリセット用のトークンのハッシュ値
+    /// `password` - ハッシュ化されたパスワード
+    /// `now` - 現在時刻
+    ///
+    /// トークンの行を `FOR UPDATE` でロックし、未使用かつ有効期限内の場合のみ、
+    /// パスワードの更新・トークンの使用済みへの更新・セッションの削除を1つのトランザクションで行う
+    ///
+    /// 成功した場合はトークンが有効だったかどうかを返し、失敗した場合は `AppError` を返す
+    async fn reset_password_with_token(
+        &self,
+        token_hash: &str,
+        password: &str,
+        now: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let reset_token: Option<(i32, i32)> = sqlx::query_as(
+            "SELECT id, user_id FROM password_reset_tokens WHERE token_hash = ? AND used_at IS NULL AND expires_at > ? FOR UPDATE",
+        )
+        .bind(token_hash)
+        .bind(now)
+        .fetch_optional(&mut tx)
+        .await?;
+        let Some((token_id, user_id)) = reset_token else {
+            return Ok(false);
+        };
+
+        sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+            .bind(password)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query("UPDATE password_reset_tokens SET used_at = ? WHERE id = ?")
+            .bind(now)
+            .bind(token_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query("DELETE FROM sessions WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
     /// IDでディスパッチャーを検索する
     ///
     /// `id` - ディスパッチャーID
@@ -183,20 +495,5 @@ impl AuthRepository for AuthRepositoryImpl {
 
         Ok(dispatcher)
     }
+}
 
-    /// 新しいディスパッチャーを作成する
-    ///
-    /// `user_id` - ユーザーID
-    /// `area_id` - エリアID
-    ///
-    /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
-    async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO dispatchers (user_id, area_id) VALUES (?, ?)")
-            .bind(user_id)
-            .bind(area_id)
-            .execute(&self.pool)
-            .await?;
-
-        Ok(())
-    }
-}
\ No newline at end of file
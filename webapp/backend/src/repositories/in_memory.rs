@@ -0,0 +1,740 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use chrono::{DateTime, Utc};
+
+use crate::domains::auth_service::AuthRepository;
+use crate::domains::map_service::MapRepository;
+use crate::domains::order_service::OrderRepository;
+use crate::domains::tow_truck_service::TowTruckRepository;
+use crate::errors::AppError;
+use crate::models::graph::{Edge, Node};
+use crate::models::order::{CancelReason, CompletedOrder, Order, OrderStatus};
+use crate::models::tow_truck::{LocationRecord, TowTruck};
+use crate::models::user::{AuthenticatedUser, Dispatcher, Role, Session, User};
+use crate::repositories::order_repository::check_dispatchable;
+use crate::utils::verify_password;
+
+/// メモリ上に保持するデータ
+///
+/// テストではフィールドに直接データを追加して初期状態を作る
+#[derive(Debug, Default)]
+pub struct InMemoryData {
+    pub users: Vec<User>,
+    pub dispatchers: Vec<Dispatcher>,
+    pub sessions: Vec<Session>,
+    pub password_reset_tokens: Vec<PasswordResetToken>,
+    pub orders: Vec<Order>,
+    pub completed_orders: Vec<CompletedOrder>,
+    pub tow_trucks: Vec<TowTruck>,
+    /// レッカー車IDと位置の履歴
+    pub locations: Vec<(i32, LocationRecord)>,
+    /// エリアIDとノード
+    pub nodes: Vec<(i32, Node)>,
+    pub edges: Vec<Edge>,
+}
+
+/// パスワードリセット用のトークンを表す構造体
+#[derive(Clone, Debug)]
+pub struct PasswordResetToken {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// 全てのリポジトリのトレイトをメモリ上のデータで実装するテスト用のリポジトリ
+///
+/// 複製したリポジトリは同じデータを共有するため、サービスに渡した後もテストからデータを確認できる。
+/// 存在しない行の扱いやステータスの確認は MySQL の実装と同じ結果を返す
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryRepository {
+    data: Arc<Mutex<InMemoryData>>,
+}
+
+impl InMemoryRepository {
+    /// データをロックして取得する
+    pub fn data(&self) -> MutexGuard<'_, InMemoryData> {
+        self.data.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 保持しているユーザーの認証済みのユーザーを作成する
+    ///
+    /// ユーザーがディスパッチャーの場合はディスパッチャー情報も含める
+    pub fn authenticated_user(&self, user_id: i32) -> AuthenticatedUser {
+        let data = self.data();
+        let user = data
+            .users
+            .iter()
+            .find(|user| user.id == user_id)
+            .cloned()
+            .expect("user should exist");
+        let dispatcher = data
+            .dispatchers
+            .iter()
+            .find(|dispatcher| dispatcher.user_id == user_id)
+            .cloned();
+        let now = Utc::now();
+        AuthenticatedUser {
+            user,
+            session: Session {
+                id: user_id,
+                user_id,
+                session_token: format!("token{}", user_id),
+                is_valid: true,
+                created_at: now,
+                last_seen_at: now,
+            },
+            dispatcher,
+        }
+    }
+}
+
+/// 条件が指定されていない、または値が条件と一致するかどうかを返す
+fn matches_filter<T: PartialEq>(filter: Option<&T>, value: &T) -> bool {
+    match filter {
+        Some(filter) => filter == value,
+        None => true,
+    }
+}
+
+impl InMemoryData {
+    fn next_session_id(&self) -> i32 {
+        self.sessions
+            .iter()
+            .map(|session| session.id)
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    fn area_id_of(&self, node_id: i32) -> Option<i32> {
+        self.nodes
+            .iter()
+            .find(|(_, node)| node.id == node_id)
+            .map(|(area_id, _)| *area_id)
+    }
+
+    fn order_mut(&mut self, order_id: i32) -> Result<&mut Order, AppError> {
+        self.orders
+            .iter_mut()
+            .find(|order| order.id == order_id)
+            .ok_or(AppError::NotFound)
+    }
+
+    fn release_tow_truck(&mut self, tow_truck_id: Option<i32>) {
+        let tow_truck = self
+            .tow_trucks
+            .iter_mut()
+            .find(|tow_truck| Some(tow_truck.id) == tow_truck_id);
+        if let Some(tow_truck) = tow_truck {
+            tow_truck.status = "available".to_string();
+        }
+    }
+}
+
+impl AuthRepository for InMemoryRepository {
+    async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        area_id: Option<i32>,
+        session_token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(User, Option<Dispatcher>), AppError> {
+        let mut data = self.data();
+        if let Some(area_id) = area_id {
+            if !data
+                .nodes
+                .iter()
+                .any(|(node_area_id, _)| *node_area_id == area_id)
+            {
+                return Err(AppError::InvalidArea(area_id));
+            }
+        }
+        if data.users.iter().any(|user| user.username == username) {
+            return Err(AppError::Conflict);
+        }
+
+        let user = User {
+            id: data.users.iter().map(|user| user.id).max().unwrap_or(0) + 1,
+            username: username.to_string(),
+            password: password.to_string(),
+            role: role.as_str().to_string(),
+        };
+        let session = Session {
+            id: data.next_session_id(),
+            user_id: user.id,
+            session_token: session_token.to_string(),
+            is_valid: true,
+            created_at: now,
+            last_seen_at: now,
+        };
+        let dispatcher = area_id.map(|area_id| Dispatcher {
+            id: data
+                .dispatchers
+                .iter()
+                .map(|dispatcher| dispatcher.id)
+                .max()
+                .unwrap_or(0)
+                + 1,
+            user_id: user.id,
+            area_id,
+        });
+
+        data.users.push(user.clone());
+        data.sessions.push(session);
+        data.dispatchers.extend(dispatcher.clone());
+        Ok((user, dispatcher))
+    }
+
+    async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError> {
+        Ok(self.data().users.iter().find(|user| user.id == id).cloned())
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError> {
+        Ok(self
+            .data()
+            .users
+            .iter()
+            .find(|user| user.username == username)
+            .cloned())
+    }
+
+    async fn authenticate_user(&self, username: &str, password: &str) -> Result<User, AppError> {
+        let user = self
+            .find_user_by_username(username)
+            .await?
+            .ok_or(AppError::Unauthorized)?;
+        match verify_password(&user.password, password)? {
+            true => Ok(user),
+            false => Err(AppError::Unauthorized),
+        }
+    }
+
+    async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError> {
+        Ok(self
+            .data()
+            .dispatchers
+            .iter()
+            .find(|dispatcher| dispatcher.id == id)
+            .cloned())
+    }
+
+    async fn find_dispatcher_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError> {
+        Ok(self
+            .data()
+            .dispatchers
+            .iter()
+            .find(|dispatcher| dispatcher.user_id == user_id)
+            .cloned())
+    }
+
+    async fn find_profile_image_name_by_user_id(
+        &self,
+        _user_id: i32,
+    ) -> Result<Option<String>, AppError> {
+        // プロフィール画像は保持しない
+        Ok(None)
+    }
+
+    async fn create_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        let session = Session {
+            id: data.next_session_id(),
+            user_id,
+            session_token: session_token.to_string(),
+            is_valid: true,
+            created_at: now,
+            last_seen_at: now,
+        };
+        data.sessions.push(session);
+        Ok(())
+    }
+
+    async fn delete_session(&self, session_token: &str) -> Result<(), AppError> {
+        self.data()
+            .sessions
+            .retain(|session| session.session_token != session_token);
+        Ok(())
+    }
+
+    async fn find_session_by_session_token(
+        &self,
+        session_token: &str,
+    ) -> Result<Session, AppError> {
+        // MySQL の実装と同様に、存在しないトークンは `RowNotFound` になる
+        self.data()
+            .sessions
+            .iter()
+            .find(|session| session.session_token == session_token)
+            .cloned()
+            .ok_or(AppError::SqlxError(sqlx::Error::RowNotFound))
+    }
+
+    async fn touch_session(
+        &self,
+        session_id: i32,
+        last_seen_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        if let Some(session) = data
+            .sessions
+            .iter_mut()
+            .find(|session| session.id == session_id)
+        {
+            session.last_seen_at = last_seen_at;
+        }
+        Ok(())
+    }
+
+    async fn rotate_session(
+        &self,
+        session_token: &str,
+        new_session_token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        let session = data
+            .sessions
+            .iter_mut()
+            .find(|session| session.session_token == session_token && session.is_valid)
+            .ok_or(AppError::Unauthorized)?;
+        session.session_token = new_session_token.to_string();
+        session.last_seen_at = now;
+        Ok(())
+    }
+
+    async fn delete_expired_sessions(
+        &self,
+        created_before: DateTime<Utc>,
+        last_seen_before: DateTime<Utc>,
+    ) -> Result<u64, AppError> {
+        let mut data = self.data();
+        let before = data.sessions.len();
+        data.sessions.retain(|session| {
+            session.created_at >= created_before && session.last_seen_at >= last_seen_before
+        });
+        Ok((before - data.sessions.len()) as u64)
+    }
+
+    async fn find_sessions_by_user_id(&self, user_id: i32) -> Result<Vec<Session>, AppError> {
+        let mut sessions: Vec<Session> = self
+            .data()
+            .sessions
+            .iter()
+            .filter(|session| session.user_id == user_id)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at));
+        Ok(sessions)
+    }
+
+    async fn delete_session_by_id(&self, user_id: i32, session_id: i32) -> Result<u64, AppError> {
+        let mut data = self.data();
+        let before = data.sessions.len();
+        data.sessions
+            .retain(|session| !(session.id == session_id && session.user_id == user_id));
+        Ok((before - data.sessions.len()) as u64)
+    }
+
+    async fn delete_sessions_by_user_id(&self, user_id: i32) -> Result<u64, AppError> {
+        let mut data = self.data();
+        let before = data.sessions.len();
+        data.sessions.retain(|session| session.user_id != user_id);
+        Ok((before - data.sessions.len()) as u64)
+    }
+
+    async fn update_password(
+        &self,
+        user_id: i32,
+        password: &str,
+        keep_session_id: i32,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        if let Some(user) = data.users.iter_mut().find(|user| user.id == user_id) {
+            user.password = password.to_string();
+        }
+        data.sessions
+            .retain(|session| session.user_id != user_id || session.id == keep_session_id);
+        Ok(())
+    }
+
+    async fn create_password_reset_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        data.password_reset_tokens
+            .retain(|reset_token| reset_token.user_id != user_id || reset_token.used_at.is_some());
+        data.password_reset_tokens.push(PasswordResetToken {
+            user_id,
+            token_hash: token_hash.to_string(),
+            expires_at,
+            used_at: None,
+        });
+        data.sessions.retain(|session| session.user_id != user_id);
+        Ok(())
+    }
+
+    async fn reset_password_with_token(
+        &self,
+        token_hash: &str,
+        password: &str,
+        now: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let mut data = self.data();
+        let Some(reset_token) = data.password_reset_tokens.iter_mut().find(|reset_token| {
+            reset_token.token_hash == token_hash
+                && reset_token.used_at.is_none()
+                && reset_token.expires_at > now
+        }) else {
+            return Ok(false);
+        };
+        reset_token.used_at = Some(now);
+        let user_id = reset_token.user_id;
+
+        if let Some(user) = data.users.iter_mut().find(|user| user.id == user_id) {
+            user.password = password.to_string();
+        }
+        data.sessions.retain(|session| session.user_id != user_id);
+        Ok(true)
+    }
+}
+
+impl OrderRepository for InMemoryRepository {
+    async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError> {
+        // MySQL の実装と同様に、存在しない注文は `RowNotFound` になる
+        self.data()
+            .orders
+            .iter()
+            .find(|order| order.id == id)
+            .cloned()
+            .ok_or(AppError::SqlxError(sqlx::Error::RowNotFound))
+    }
+
+    async fn update_order_status(
+        &self,
+        order_id: i32,
+        from_status: OrderStatus,
+        to_status: OrderStatus,
+        _changed_by: i32,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        let order = data.order_mut(order_id)?;
+        if order.status != from_status.as_str() {
+            return Err(AppError::Conflict);
+        }
+        order.status = to_status.as_str().to_string();
+        Ok(())
+    }
+
+    async fn get_paginated_orders(
+        &self,
+        page: i32,
+        page_size: i32,
+        _sort_by: Option<String>,
+        _sort_order: Option<String>,
+        status: Option<String>,
+        area: Option<i32>,
+    ) -> Result<Vec<Order>, AppError> {
+        let data = self.data();
+        let orders = data.orders.iter().filter(|order| {
+            matches_filter(status.as_ref(), &order.status)
+                && matches_filter(area.map(Some).as_ref(), &data.area_id_of(order.node_id))
+        });
+        let orders: Vec<Order> = match page_size {
+            -1 => orders.cloned().collect(),
+            page_size => orders
+                .skip((page * page_size) as usize)
+                .take(page_size as usize)
+                .cloned()
+                .collect(),
+        };
+        Ok(orders)
+    }
+
+    async fn create_order(
+        &self,
+        customer_id: i32,
+        node_id: i32,
+        car_value: f64,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        let order = Order {
+            id: data.orders.iter().map(|order| order.id).max().unwrap_or(0) + 1,
+            client_id: customer_id,
+            dispatcher_id: None,
+            tow_truck_id: None,
+            status: OrderStatus::Pending.as_str().to_string(),
+            node_id,
+            car_value,
+            order_time: Utc::now(),
+            completed_time: None,
+        };
+        data.orders.push(order);
+        Ok(())
+    }
+
+    async fn dispatch_order(
+        &self,
+        order_id: i32,
+        dispatcher_id: i32,
+        tow_truck_id: i32,
+        completed_time: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        let order_status = data
+            .orders
+            .iter()
+            .find(|order| order.id == order_id)
+            .map(|order| order.status.clone());
+        check_dispatchable(order_status.as_deref(), "pending")?;
+        let tow_truck = data
+            .tow_trucks
+            .iter_mut()
+            .find(|tow_truck| tow_truck.id == tow_truck_id);
+        check_dispatchable(
+            tow_truck
+                .as_ref()
+                .map(|tow_truck| tow_truck.status.as_str()),
+            "available",
+        )?;
+        if let Some(tow_truck) = tow_truck {
+            tow_truck.status = "busy".to_string();
+        }
+
+        let order = data.order_mut(order_id)?;
+        order.dispatcher_id = Some(dispatcher_id);
+        order.tow_truck_id = Some(tow_truck_id);
+        order.status = OrderStatus::Dispatched.as_str().to_string();
+        let order_time = order.order_time;
+        let car_value = order.car_value;
+
+        let completed_order = CompletedOrder {
+            id: data.completed_orders.len() as i32 + 1,
+            order_id,
+            tow_truck_id,
+            order_time: Some(order_time),
+            completed_time,
+            car_value,
+        };
+        data.completed_orders.push(completed_order);
+        Ok(())
+    }
+
+    async fn complete_order(
+        &self,
+        order_id: i32,
+        _completed_by: i32,
+        completed_time: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        let order = data.order_mut(order_id)?;
+        let current_status: OrderStatus = order.status.parse()?;
+        if !current_status.can_transition_to(OrderStatus::Completed) {
+            return Err(AppError::InvalidOrderStatusTransition {
+                from: current_status,
+                to: OrderStatus::Completed,
+            });
+        }
+        order.status = OrderStatus::Completed.as_str().to_string();
+        order.completed_time = Some(completed_time);
+        let tow_truck_id = order.tow_truck_id;
+        for completed_order in data
+            .completed_orders
+            .iter_mut()
+            .filter(|completed_order| completed_order.order_id == order_id)
+        {
+            completed_order.completed_time = completed_time;
+        }
+
+        data.release_tow_truck(tow_truck_id);
+        Ok(())
+    }
+
+    async fn cancel_order(
+        &self,
+        order_id: i32,
+        from_status: OrderStatus,
+        _reason: CancelReason,
+        _cancelled_by: i32,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        let order = data.order_mut(order_id)?;
+        if order.status != from_status.as_str() {
+            return Err(AppError::Conflict);
+        }
+        order.status = OrderStatus::Cancelled.as_str().to_string();
+        let tow_truck_id = order.tow_truck_id;
+
+        data.release_tow_truck(tow_truck_id);
+        Ok(())
+    }
+
+    async fn get_all_completed_orders(&self) -> Result<Vec<CompletedOrder>, AppError> {
+        let data = self.data();
+        let cancelled = |order_id: i32| {
+            data.orders.iter().any(|order| {
+                order.id == order_id && order.status == OrderStatus::Cancelled.as_str()
+            })
+        };
+        Ok(data
+            .completed_orders
+            .iter()
+            .filter(|completed_order| !cancelled(completed_order.order_id))
+            .cloned()
+            .collect())
+    }
+}
+
+impl TowTruckRepository for InMemoryRepository {
+    async fn get_paginated_tow_trucks(
+        &self,
+        page: i32,
+        page_size: i32,
+        status: Option<String>,
+        area_id: Option<i32>,
+    ) -> Result<Vec<TowTruck>, AppError> {
+        let data = self.data();
+        let tow_trucks = data.tow_trucks.iter().filter(|tow_truck| {
+            matches_filter(status.as_ref(), &tow_truck.status)
+                && matches_filter(area_id.as_ref(), &tow_truck.area_id)
+        });
+        let tow_trucks: Vec<TowTruck> = match page_size {
+            -1 => tow_trucks.cloned().collect(),
+            page_size => tow_trucks
+                .skip((page * page_size) as usize)
+                .take(page_size as usize)
+                .cloned()
+                .collect(),
+        };
+        Ok(tow_trucks)
+    }
+
+    async fn update_location(
+        &self,
+        truck_id: i32,
+        node_id: i32,
+        timestamp: DateTime<Utc>,
+        implausible: bool,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        let (x, y) = data
+            .nodes
+            .iter()
+            .find(|(_, node)| node.id == node_id)
+            .map(|(_, node)| (node.x, node.y))
+            .ok_or(AppError::SqlxError(sqlx::Error::RowNotFound))?;
+        data.locations.push((
+            truck_id,
+            LocationRecord {
+                node_id,
+                x,
+                y,
+                timestamp: Some(timestamp),
+                implausible,
+            },
+        ));
+        if let Some(tow_truck) = data
+            .tow_trucks
+            .iter_mut()
+            .find(|tow_truck| tow_truck.id == truck_id)
+        {
+            tow_truck.node_id = node_id;
+            tow_truck.location_updated_at = timestamp;
+        }
+        Ok(())
+    }
+
+    async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError> {
+        Ok(self
+            .data()
+            .tow_trucks
+            .iter()
+            .find(|tow_truck| tow_truck.id == id)
+            .cloned())
+    }
+
+    async fn find_location_history(
+        &self,
+        tow_truck_id: i32,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i32,
+    ) -> Result<Vec<LocationRecord>, AppError> {
+        // MySQL の実装と同様に、期間を指定した場合は記録時刻のない履歴を含めない
+        let in_range = |timestamp: Option<DateTime<Utc>>| match (timestamp, from, to) {
+            (_, None, None) => true,
+            (None, _, _) => false,
+            (Some(timestamp), from, to) => {
+                from.into_iter().all(|from| timestamp >= from)
+                    && to.into_iter().all(|to| timestamp <= to)
+            }
+        };
+        Ok(self
+            .data()
+            .locations
+            .iter()
+            .filter(|(id, record)| *id == tow_truck_id && in_range(record.timestamp))
+            .map(|(_, record)| record.clone())
+            .take(limit as usize)
+            .collect())
+    }
+}
+
+impl MapRepository for InMemoryRepository {
+    async fn get_all_nodes(&self, area_id: Option<i32>) -> Result<Vec<Node>, sqlx::Error> {
+        Ok(self
+            .data()
+            .nodes
+            .iter()
+            .filter(|(node_area_id, _)| matches_filter(area_id.as_ref(), node_area_id))
+            .map(|(_, node)| node.clone())
+            .collect())
+    }
+
+    async fn get_all_edges(&self, area_id: Option<i32>) -> Result<Vec<Edge>, sqlx::Error> {
+        let data = self.data();
+        Ok(data
+            .edges
+            .iter()
+            .filter(|edge| {
+                matches_filter(area_id.map(Some).as_ref(), &data.area_id_of(edge.node_a_id))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<i32, sqlx::Error> {
+        self.data()
+            .area_id_of(node_id)
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    async fn update_edge(
+        &self,
+        node_a_id: i32,
+        node_b_id: i32,
+        weight: i32,
+    ) -> Result<(), sqlx::Error> {
+        for edge in self.data().edges.iter_mut() {
+            if (edge.node_a_id, edge.node_b_id) == (node_a_id, node_b_id)
+                || (edge.node_a_id, edge.node_b_id) == (node_b_id, node_a_id)
+            {
+                edge.weight = weight;
+            }
+        }
+        Ok(())
+    }
+}
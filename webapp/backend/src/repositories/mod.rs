@@ -1,4 +1,7 @@
 pub mod auth_repository;
+#[cfg(test)]
+pub mod in_memory;
 pub mod map_repository;
 pub mod order_repository;
 pub mod tow_truck_repository;
+pub mod query_builder;
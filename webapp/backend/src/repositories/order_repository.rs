@@ -1,6 +1,7 @@
 use crate::domains::order_service::OrderRepository;
 use crate::errors::AppError;
-use crate::models::order::{CompletedOrder, Order};
+use crate::models::order::{CancelReason, CompletedOrder, Order, OrderStatus};
+use crate::repositories::query_builder::SelectQueryBuilder;
 use chrono::{DateTime, Utc};
 use sqlx::mysql::MySqlPool;
 
@@ -41,18 +42,45 @@ impl OrderRepository for OrderRepositoryImpl {
         Ok(order)
     }
 
-    /// 注文のステータスを更新する
+    /// 注文のステータスを更新し、遷移履歴を記録する
     ///
     /// `order_id` - 注文ID
-    /// `status` - 新しいステータス
+    /// `from_status` - 更新前のステータス
+    /// `to_status` - 新しいステータス
+    /// `changed_by` - ステータスを変更したユーザーID
     ///
     /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
-    async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError> {
-        sqlx::query("UPDATE orders SET status = ? WHERE id = ?")
-            .bind(status)
+    /// - 注文のステータスが既に `from_status` から変更されていた場合は `AppError::Conflict`
+    async fn update_order_status(
+        &self,
+        order_id: i32,
+        from_status: OrderStatus,
+        to_status: OrderStatus,
+        changed_by: i32,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query("UPDATE orders SET status = ? WHERE id = ? AND status = ?")
+            .bind(to_status.as_str())
             .bind(order_id)
-            .execute(&self.pool)
+            .bind(from_status.as_str())
+            .execute(&mut tx)
             .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::Conflict);
+        }
+
+        sqlx::query(
+            "INSERT INTO order_status_histories (order_id, from_status, to_status, changed_by) VALUES (?, ?, ?, ?)",
+        )
+        .bind(order_id)
+        .bind(from_status.as_str())
+        .bind(to_status.as_str())
+        .bind(changed_by)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
 
         Ok(())
     }
@@ -76,29 +104,22 @@ impl OrderRepository for OrderRepositoryImpl {
         status: Option<String>,
         area: Option<i32>,
     ) -> Result<Vec<Order>, AppError> {
-        let offset = page * page_size;
-        let order_clause = format!(
-            "ORDER BY {} {}",
-            match sort_by.as_deref() {
-                Some("car_value") => "o.car_value",
-                Some("status") => "o.status",
-                Some("order_time") => "o.order_time",
-                _ => "o.order_time",
-            },
-            match sort_order.as_deref() {
-                Some("DESC") | Some("desc") => "DESC",
-                _ => "ASC",
-            }
-        );
-
-        let where_clause = match (status.clone(), area) {
-            (Some(_), Some(_)) => "WHERE o.status = ? AND n.area_id = ?".to_string(),
-            (None, Some(_)) => "WHERE n.area_id = ?".to_string(),
-            (Some(_), None) => "WHERE o.status = ?".to_string(),
-            _ => "".to_string(),
+        let is_desc = matches!(sort_order.as_deref(), Some("DESC") | Some("desc"));
+        let order_by = match (sort_by.as_deref(), is_desc) {
+            (Some("car_value"), false) => "o.car_value ASC",
+            (Some("car_value"), true) => "o.car_value DESC",
+            (Some("status"), false) => "o.status ASC",
+            (Some("status"), true) => "o.status DESC",
+            (_, false) => "o.order_time ASC",
+            (_, true) => "o.order_time DESC",
         };
 
-        let sql = format!(
+        let query = SelectQueryBuilder::new()
+            .filter_some("o.status = ?", status)
+            .filter_some("n.area_id = ?", area)
+            .order_by(order_by)
+            .paginate(page, page_size);
+        let sql = query.sql(
             "SELECT 
                 o.id, 
                 o.client_id, 
@@ -114,48 +135,13 @@ impl OrderRepository for OrderRepositoryImpl {
             JOIN
                 nodes n
             ON 
-                o.node_id = n.id
-            {} 
-            {} 
-            LIMIT ? 
-            OFFSET ?",
-            where_clause, order_clause
+                o.node_id = n.id",
         );
 
-        let orders = match (status, area) {
-            (Some(status), Some(area)) => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(status)
-                    .bind(area)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            (None, Some(area)) => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(area)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            (Some(status), None) => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(status)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            _ => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-        };
+        let orders = query
+            .bind(sqlx::query_as::<_, Order>(&sql))
+            .fetch_all(&self.pool)
+            .await?;
 
         Ok(orders)
     }
@@ -183,66 +169,281 @@ impl OrderRepository for OrderRepositoryImpl {
         Ok(())
     }
 
-    /// 注文のディスパッチ情報を更新する
+    /// 注文にレッカー車を割り当てる
     ///
-    /// `id` - 注文ID
+    /// `order_id` - 注文ID
     /// `dispatcher_id` - ディスパッチャーID
     /// `tow_truck_id` - レッカー車ID
+    /// `completed_time` - 完了した注文に仮に記録する完了時間（注文の完了時に更新される）
+    ///
+    /// 注文とレッカー車の行を `FOR UPDATE` でロックし、注文が `pending` かつレッカー車が `available` の
+    /// 場合のみ、完了した注文の作成・注文の更新・レッカー車の更新・遷移履歴の記録を1つのトランザクションで行う
     ///
     /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
-    async fn update_order_dispatched(
+    /// - 注文またはレッカー車が存在しない場合は `AppError::NotFound`
+    /// - 注文が割り当て済み、またはレッカー車が利用中の場合は `AppError::Conflict`
+    async fn dispatch_order(
         &self,
-        id: i32,
+        order_id: i32,
         dispatcher_id: i32,
         tow_truck_id: i32,
+        completed_time: DateTime<Utc>,
     ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        // デッドロックを避けるため、常に注文、レッカー車の順にロックする
+        let order_status: Option<String> =
+            sqlx::query_scalar("SELECT status FROM orders WHERE id = ? FOR UPDATE")
+                .bind(order_id)
+                .fetch_optional(&mut tx)
+                .await?;
+        check_dispatchable(order_status.as_deref(), "pending")?;
+
+        let tow_truck_status: Option<String> =
+            sqlx::query_scalar("SELECT status FROM tow_trucks WHERE id = ? FOR UPDATE")
+                .bind(tow_truck_id)
+                .fetch_optional(&mut tx)
+                .await?;
+        check_dispatchable(tow_truck_status.as_deref(), "available")?;
+
+        sqlx::query("INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)")
+            .bind(order_id)
+            .bind(tow_truck_id)
+            .bind(completed_time)
+            .execute(&mut tx)
+            .await?;
+
         sqlx::query(
             "UPDATE orders SET dispatcher_id = ?, tow_truck_id = ?, status = 'dispatched' WHERE id = ?",
         )
         .bind(dispatcher_id)
         .bind(tow_truck_id)
-        .bind(id)
-        .execute(&self.pool)
+        .bind(order_id)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query("UPDATE tow_trucks SET status = 'busy' WHERE id = ?")
+            .bind(tow_truck_id)
+            .execute(&mut tx)
+            .await?;
+
+        // 割り当てを行ったディスパッチャーのユーザーを変更者として遷移履歴を記録する
+        sqlx::query(
+            "INSERT INTO order_status_histories (order_id, from_status, to_status, changed_by)
+            SELECT ?, 'pending', 'dispatched', user_id FROM dispatchers WHERE id = ?",
+        )
+        .bind(order_id)
+        .bind(dispatcher_id)
+        .execute(&mut tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(())
     }
 
-    /// 完了した注文を作成する
+    /// 注文を完了する
     ///
     /// `order_id` - 注文ID
-    /// `tow_truck_id` - レッカー車ID
+    /// `completed_by` - 注文を完了したユーザーID
     /// `completed_time` - 完了時間
     ///
+    /// 注文の行を `FOR UPDATE` でロックし、注文が `towing` の場合のみ、注文と完了した注文の完了時間の更新・
+    /// レッカー車の解放・遷移履歴の記録を1つのトランザクションで行う
+    ///
     /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
-    async fn create_completed_order(
+    /// - 注文が存在しない場合は `AppError::NotFound`
+    /// - 注文が `towing` 以外の場合は `AppError::InvalidOrderStatusTransition`
+    async fn complete_order(
         &self,
         order_id: i32,
-        tow_truck_id: i32,
+        completed_by: i32,
         completed_time: DateTime<Utc>,
     ) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)")
+        let mut tx = self.pool.begin().await?;
+
+        let order: Option<(String, Option<i32>)> =
+            sqlx::query_as("SELECT status, tow_truck_id FROM orders WHERE id = ? FOR UPDATE")
+                .bind(order_id)
+                .fetch_optional(&mut tx)
+                .await?;
+        let (status, tow_truck_id) = order.ok_or(AppError::NotFound)?;
+        let current_status: OrderStatus = status.parse()?;
+        if !current_status.can_transition_to(OrderStatus::Completed) {
+            return Err(AppError::InvalidOrderStatusTransition {
+                from: current_status,
+                to: OrderStatus::Completed,
+            });
+        }
+
+        sqlx::query("UPDATE orders SET status = 'completed', completed_time = ? WHERE id = ?")
+            .bind(completed_time)
             .bind(order_id)
-            .bind(tow_truck_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query("UPDATE completed_orders SET completed_time = ? WHERE order_id = ?")
             .bind(completed_time)
-            .execute(&self.pool)
+            .bind(order_id)
+            .execute(&mut tx)
+            .await?;
+
+        if let Some(tow_truck_id) = tow_truck_id {
+            sqlx::query("UPDATE tow_trucks SET status = 'available' WHERE id = ?")
+                .bind(tow_truck_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO order_status_histories (order_id, from_status, to_status, changed_by) VALUES (?, ?, 'completed', ?)",
+        )
+        .bind(order_id)
+        .bind(current_status.as_str())
+        .bind(completed_by)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// 注文をキャンセルする
+    ///
+    /// `order_id` - 注文ID
+    /// `from_status` - キャンセル前のステータス
+    /// `reason` - キャンセル理由
+    /// `cancelled_by` - 注文をキャンセルしたユーザーID
+    ///
+    /// 注文の行を `FOR UPDATE` でロックし、注文のステータスが `from_status` の場合のみ、注文の更新・
+    /// キャンセル理由の記録・レッカー車の解放・遷移履歴の記録を1つのトランザクションで行う
+    ///
+    /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
+    /// - 注文が存在しない場合は `AppError::NotFound`
+    /// - 注文のステータスが既に `from_status` から変更されていた場合は `AppError::Conflict`
+    async fn cancel_order(
+        &self,
+        order_id: i32,
+        from_status: OrderStatus,
+        reason: CancelReason,
+        cancelled_by: i32,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let order: Option<(String, Option<i32>)> =
+            sqlx::query_as("SELECT status, tow_truck_id FROM orders WHERE id = ? FOR UPDATE")
+                .bind(order_id)
+                .fetch_optional(&mut tx)
+                .await?;
+        let (status, tow_truck_id) = order.ok_or(AppError::NotFound)?;
+        if status != from_status.as_str() {
+            return Err(AppError::Conflict);
+        }
+
+        sqlx::query("UPDATE orders SET status = 'cancelled' WHERE id = ?")
+            .bind(order_id)
+            .execute(&mut tx)
             .await?;
 
+        sqlx::query(
+            "INSERT INTO order_cancellations (order_id, reason, cancelled_by) VALUES (?, ?, ?)",
+        )
+        .bind(order_id)
+        .bind(reason.as_str())
+        .bind(cancelled_by)
+        .execute(&mut tx)
+        .await?;
+
+        if let Some(tow_truck_id) = tow_truck_id {
+            sqlx::query("UPDATE tow_trucks SET status = 'available' WHERE id = ?")
+                .bind(tow_truck_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO order_status_histories (order_id, from_status, to_status, changed_by) VALUES (?, ?, 'cancelled', ?)",
+        )
+        .bind(order_id)
+        .bind(from_status.as_str())
+        .bind(cancelled_by)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 
     /// 全ての完了した注文を取得する
     ///
+    /// キャンセルされた注文は含まない
+    ///
     /// 成功した場合は `Vec<CompletedOrder>` を返し、失敗した場合は `AppError` を返す
     async fn get_all_completed_orders(&self) -> Result<Vec<CompletedOrder>, AppError> {
         let orders = sqlx::query_as::<_, CompletedOrder>(
-            "SELECT co.id, co.order_id, co.tow_truck_id, co.order_time, co.completed_time, o.car_value
+            "SELECT co.id, co.order_id, co.tow_truck_id, o.order_time, co.completed_time, o.car_value
                     FROM completed_orders co
-                    JOIN orders o ON co.order_id = o.id"
+                    JOIN orders o ON co.order_id = o.id
+                    WHERE o.status <> 'cancelled'"
             )
             .fetch_all(&self.pool)
             .await?;
 
         Ok(orders)
     }
-}
\ No newline at end of file
+}
+
+/// ロックした行のステータスが割り当て可能なステータスかどうかを確認する
+///
+/// `status` - ロックした行のステータス（行が存在しない場合は `None`）
+/// `dispatchable_status` - 割り当て可能なステータス
+///
+/// 同時に割り当てを行った場合、後からロックを取得した側は先に割り当てた側の更新後のステータスを読むため、
+/// ここで `AppError::Conflict` になる。行が存在しない場合は `AppError::NotFound` を返す
+pub fn check_dispatchable(status: Option<&str>, dispatchable_status: &str) -> Result<(), AppError> {
+    match status {
+        Some(status) if status == dispatchable_status => Ok(()),
+        Some(_) => Err(AppError::Conflict),
+        None => Err(AppError::NotFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatches_pending_order_to_available_tow_truck() {
+        assert!(check_dispatchable(Some("pending"), "pending").is_ok());
+        assert!(check_dispatchable(Some("available"), "available").is_ok());
+    }
+
+    #[test]
+    fn rejects_second_dispatch_after_first_commits() {
+        // 先に割り当てた側のコミット後、注文は `dispatched`、レッカー車は `busy` になっている
+        for (status, dispatchable_status) in [
+            ("dispatched", "pending"),
+            ("cancelled", "pending"),
+            ("busy", "available"),
+        ] {
+            assert!(
+                matches!(
+                    check_dispatchable(Some(status), dispatchable_status),
+                    Err(AppError::Conflict)
+                ),
+                "{} should not be dispatchable",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_missing_rows() {
+        assert!(matches!(
+            check_dispatchable(None, "pending"),
+            Err(AppError::NotFound)
+        ));
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use sqlx::mysql::{MySql, MySqlArguments};
+use sqlx::query::QueryAs;
+
+/// クエリにバインドする値
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryValue {
+    Int(i32),
+    Text(String),
+    DateTime(DateTime<Utc>),
+}
+
+impl From<i32> for QueryValue {
+    fn from(value: i32) -> Self {
+        QueryValue::Int(value)
+    }
+}
+
+impl From<String> for QueryValue {
+    fn from(value: String) -> Self {
+        QueryValue::Text(value)
+    }
+}
+
+impl From<DateTime<Utc>> for QueryValue {
+    fn from(value: DateTime<Utc>) -> Self {
+        QueryValue::DateTime(value)
+    }
+}
+
+/// 絞り込みとページネーションを行う SELECT クエリを組み立てる構造体
+///
+/// SQL の断片は `&'static str` のみ受け付け、リクエストから受け取った値は全てプレースホルダーにバインドする
+#[derive(Debug, Default)]
+pub struct SelectQueryBuilder {
+    conditions: Vec<&'static str>,
+    values: Vec<QueryValue>,
+    order_by: Option<&'static str>,
+    pagination: Option<(i32, i32)>,
+}
+
+impl SelectQueryBuilder {
+    /// 新しい `SelectQueryBuilder` を作成する
+    pub fn new() -> Self {
+        SelectQueryBuilder::default()
+    }
+
+    /// プレースホルダーを1つ含む WHERE 句の条件を追加する
+    ///
+    /// `condition` - `?` を1つ含む条件の SQL
+    /// `value` - プレースホルダーにバインドする値
+    pub fn filter(mut self, condition: &'static str, value: impl Into<QueryValue>) -> Self {
+        self.conditions.push(condition);
+        self.values.push(value.into());
+        self
+    }
+
+    /// 値が指定されている場合のみ条件を追加する
+    ///
+    /// `condition` - `?` を1つ含む条件の SQL
+    /// `value` - プレースホルダーにバインドする値
+    pub fn filter_some<V: Into<QueryValue>>(self, condition: &'static str, value: Option<V>) -> Self {
+        match value {
+            Some(value) => self.filter(condition, value),
+            None => self,
+        }
+    }
+
+    /// ORDER BY 句を設定する
+    ///
+    /// `order_by` - 並び順の SQL（`ORDER BY` は含めない）
+    pub fn order_by(mut self, order_by: &'static str) -> Self {
+        self.order_by = Some(order_by);
+        self
+    }
+
+    /// LIMIT 句と OFFSET 句を設定する
+    ///
+    /// `page` - ページ番号（0以上）
+    /// `page_size` - 1ページあたりの件数（1以上、`-1` の場合は全件を取得する）
+    ///
+    /// 値の検証は呼び出し側で行う。OFFSET が `i32` で表せない場合は結果が空になるよう上限に丸める
+    pub fn paginate(mut self, page: i32, page_size: i32) -> Self {
+        self.pagination = match page_size {
+            -1 => None,
+            page_size => Some((page_size, page.checked_mul(page_size).unwrap_or(i32::MAX))),
+        };
+        self
+    }
+
+    /// SQL を組み立てる
+    ///
+    /// `select` - `SELECT` から `FROM` 句と `JOIN` 句までの SQL
+    pub fn sql(&self, select: &str) -> String {
+        let mut sql = select.trim_end().to_string();
+        if !self.conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.conditions.join(" AND "));
+        }
+        if let Some(order_by) = self.order_by {
+            sql.push_str(" ORDER BY ");
+            sql.push_str(order_by);
+        }
+        if self.pagination.is_some() {
+            sql.push_str(" LIMIT ? OFFSET ?");
+        }
+        sql
+    }
+
+    /// プレースホルダーにバインドする値を SQL 中の順に返す
+    pub fn values(&self) -> Vec<QueryValue> {
+        let mut values = self.values.clone();
+        if let Some((limit, offset)) = self.pagination {
+            values.push(QueryValue::Int(limit));
+            values.push(QueryValue::Int(offset));
+        }
+        values
+    }
+
+    /// `sql` で組み立てたクエリに値をバインドする
+    ///
+    /// `query` - `sql` で組み立てた SQL のクエリ
+    pub fn bind<'q, O>(
+        &self,
+        query: QueryAs<'q, MySql, O, MySqlArguments>,
+    ) -> QueryAs<'q, MySql, O, MySqlArguments> {
+        self.values()
+            .into_iter()
+            .fold(query, |query, value| match value {
+                QueryValue::Int(value) => query.bind(value),
+                QueryValue::Text(value) => query.bind(value),
+                QueryValue::DateTime(value) => query.bind(value),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SELECT: &str = "SELECT tt.id FROM tow_trucks tt";
+
+    const MALICIOUS_STATUSES: [&str; 4] = [
+        "available' OR '1'='1",
+        "'; DROP TABLE tow_trucks; --",
+        "available' UNION SELECT id, password FROM users --",
+        "\\' OR 1=1 #",
+    ];
+
+    fn tow_truck_query(status: Option<&str>, area_id: Option<i32>) -> SelectQueryBuilder {
+        SelectQueryBuilder::new()
+            .filter_some("tt.status = ?", status.map(str::to_string))
+            .filter_some("tt.area_id = ?", area_id)
+            .order_by("tt.id ASC")
+            .paginate(2, 10)
+    }
+
+    #[test]
+    fn builds_where_order_and_pagination_clauses() {
+        let query = tow_truck_query(Some("available"), Some(1));
+
+        assert_eq!(
+            query.sql(SELECT),
+            "SELECT tt.id FROM tow_trucks tt WHERE tt.status = ? AND tt.area_id = ? ORDER BY tt.id ASC LIMIT ? OFFSET ?"
+        );
+        assert_eq!(
+            query.values(),
+            vec![
+                QueryValue::Text("available".to_string()),
+                QueryValue::Int(1),
+                QueryValue::Int(10),
+                QueryValue::Int(20),
+            ]
+        );
+    }
+
+    #[test]
+    fn omits_missing_filters_and_unlimited_pagination() {
+        let query = SelectQueryBuilder::new()
+            .filter_some("tt.status = ?", None::<String>)
+            .paginate(0, -1);
+
+        assert_eq!(query.sql(SELECT), "SELECT tt.id FROM tow_trucks tt");
+        assert!(query.values().is_empty());
+    }
+
+    #[test]
+    fn clamps_overflowing_offset() {
+        let query = SelectQueryBuilder::new().paginate(i32::MAX, 10);
+
+        assert_eq!(
+            query.values(),
+            vec![QueryValue::Int(10), QueryValue::Int(i32::MAX)]
+        );
+    }
+
+    #[test]
+    fn binds_malicious_status_instead_of_embedding_it() {
+        let expected_sql = tow_truck_query(Some("available"), Some(1)).sql(SELECT);
+
+        for status in MALICIOUS_STATUSES {
+            let query = tow_truck_query(Some(status), Some(1));
+
+            assert_eq!(query.sql(SELECT), expected_sql);
+            assert!(!query.sql(SELECT).contains(status));
+            assert_eq!(query.values()[0], QueryValue::Text(status.to_string()));
+        }
+    }
+}
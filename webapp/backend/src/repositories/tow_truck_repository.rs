@@ -1,6 +1,8 @@
 use crate::domains::tow_truck_service::TowTruckRepository;
 use crate::errors::AppError;
-use crate::models::tow_truck::TowTruck;
+use crate::models::tow_truck::{LocationRecord, TowTruck};
+use crate::repositories::query_builder::SelectQueryBuilder;
+use chrono::{DateTime, Utc};
 use sqlx::mysql::MySqlPool;
 
 /// レッカー車リポジトリの実装構造体
@@ -34,45 +36,21 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
         status: Option<String>,
         area_id: Option<i32>,
     ) -> Result<Vec<TowTruck>, AppError> {
-        // WHERE句を動的に作成
-        let where_clause = match (status, area_id) {
-            (Some(status), Some(area_id)) => format!(
-                "WHERE tt.status = '{}' AND tt.area_id = {} AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-                status, area_id
-            ),
-            (None, Some(area_id)) => format!(
-                "WHERE tt.area_id = {} AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-                area_id
-            ),
-            (Some(status), None) => format!(
-                "WHERE tt.status = '{}' AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-                status
-            ),
-            (None, None) => "WHERE l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)"
-                .to_string(),
-        };
-
-        // LIMIT句を動的に作成
-        let limit_clause = match page_size {
-            -1 => "".to_string(),
-            _ => format!("LIMIT {}", page_size),
-        };
-
-        // OFFSET句を動的に作成
-        let offset_clause = match page_size {
-            -1 => "".to_string(),
-            page_size => format!("OFFSET {}", page * page_size),
-        };
-
-        // SQLクエリを作成
-        let query = format!(
+        // 条件の値は全てプレースホルダーにバインドする
+        let query = SelectQueryBuilder::new()
+            .filter_some("tt.status = ?", status)
+            .filter_some("tt.area_id = ?", area_id)
+            .order_by("tt.id ASC")
+            .paginate(page, page_size);
+        let sql = query.sql(
             "SELECT
                 tt.id,
                 tt.driver_id,
                 u.username AS driver_username,
                 tt.status,
                 tt.area_id,
-                l.node_id
+                cl.node_id,
+                cl.updated_at AS location_updated_at
             FROM
                 tow_trucks tt
             JOIN
@@ -80,19 +58,14 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
             ON
                 tt.driver_id = u.id
             JOIN 
-                locations l
+                current_locations cl
             ON 
-                tt.id = l.tow_truck_id
-            {}
-            ORDER BY
-                tt.id ASC
-            {}
-            {}",
-            where_clause, limit_clause, offset_clause
+                tt.id = cl.tow_truck_id",
         );
 
         // SQLクエリを実行し、結果を取得
-        let tow_trucks = sqlx::query_as::<_, TowTruck>(&query)
+        let tow_trucks = query
+            .bind(sqlx::query_as::<_, TowTruck>(&sql))
             .fetch_all(&self.pool)
             .await?;
 
@@ -103,29 +76,42 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
     ///
     /// `tow_truck_id` - レッカー車ID
     /// `node_id` - ノードID
+    /// `timestamp` - 位置を記録した時刻
+    /// `implausible` - 経過時間内に到達できない移動かどうか（履歴にのみ記録する）
     ///
-    /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
-    async fn update_location(&self, tow_truck_id: i32, node_id: i32) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO locations (tow_truck_id, node_id) VALUES (?, ?)")
-            .bind(tow_truck_id)
-            .bind(node_id)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
-    }
-
-    /// レッカー車のステータスを更新する
-    ///
-    /// `tow_truck_id` - レッカー車ID
-    /// `status` - 新しいステータス
+    /// 履歴（`locations`）への追加と現在位置（`current_locations`）の更新を1つのトランザクションで行う
     ///
     /// 成功した場合は `()` を返し、失敗した場合は `AppError` を返す
-    async fn update_status(&self, tow_truck_id: i32, status: &str) -> Result<(), AppError> {
-        sqlx::query("UPDATE tow_trucks SET status = ? WHERE id = ?")
-            .bind(status)
-            .bind(tow_truck_id)
-            .execute(&self.pool)
-            .await?;
+    async fn update_location(
+        &self,
+        tow_truck_id: i32,
+        node_id: i32,
+        timestamp: DateTime<Utc>,
+        implausible: bool,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO locations (tow_truck_id, node_id, timestamp, implausible) VALUES (?, ?, ?, ?)",
+        )
+        .bind(tow_truck_id)
+        .bind(node_id)
+        .bind(timestamp)
+        .bind(implausible)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO current_locations (tow_truck_id, node_id, updated_at) VALUES (?, ?, ?)
+            ON DUPLICATE KEY UPDATE node_id = VALUES(node_id), updated_at = VALUES(updated_at)",
+        )
+        .bind(tow_truck_id)
+        .bind(node_id)
+        .bind(timestamp)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
 
         Ok(())
     }
@@ -138,7 +124,8 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
     async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError> {
         let tow_truck = sqlx::query_as::<_, TowTruck>(
             "SELECT
-                tt.id, tt.driver_id, u.username AS driver_username, tt.status, l.node_id, tt.area_id
+                tt.id, tt.driver_id, u.username AS driver_username, tt.status, cl.node_id, tt.area_id,
+                cl.updated_at AS location_updated_at
             FROM
                 tow_trucks tt
             JOIN
@@ -146,13 +133,11 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
             ON
                 tt.driver_id = u.id
             JOIN
-                locations l
+                current_locations cl
             ON
-                tt.id = l.tow_truck_id
+                tt.id = cl.tow_truck_id
             WHERE
-                tt.id = ?
-            AND
-                l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
+                tt.id = ?",
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -160,4 +145,48 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
 
         Ok(tow_truck)
     }
-}
\ No newline at end of file
+
+    /// レッカー車の位置の履歴を古い順に取得する
+    ///
+    /// `tow_truck_id` - レッカー車ID
+    /// `from` - 取得する期間の開始時刻（指定しない場合は最初から）
+    /// `to` - 取得する期間の終了時刻（指定しない場合は最後まで）
+    /// `limit` - 取得する最大件数
+    ///
+    /// 成功した場合は `Vec<LocationRecord>` を返し、失敗した場合は `AppError` を返す
+    async fn find_location_history(
+        &self,
+        tow_truck_id: i32,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i32,
+    ) -> Result<Vec<LocationRecord>, AppError> {
+        let query = SelectQueryBuilder::new()
+            .filter("l.tow_truck_id = ?", tow_truck_id)
+            .filter_some("l.timestamp >= ?", from)
+            .filter_some("l.timestamp <= ?", to)
+            .order_by("l.timestamp ASC, l.id ASC")
+            .paginate(0, limit);
+        let sql = query.sql(
+            "SELECT
+                l.node_id,
+                n.x,
+                n.y,
+                l.timestamp,
+                l.implausible
+            FROM
+                locations l
+            JOIN
+                nodes n
+            ON
+                l.node_id = n.id",
+        );
+
+        let locations = query
+            .bind(sqlx::query_as::<_, LocationRecord>(&sql))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(locations)
+    }
+}
@@ -3,6 +3,7 @@ use argon2::{
     Argon2,
 };
 use rand::Rng;
+use sha2::{Digest, Sha256};
 
 use crate::errors::AppError;
 
@@ -24,6 +25,19 @@ pub fn generate_session_token() -> String {
     token
 }
 
+/// トークンをハッシュ化する関数
+///
+/// `token` - ハッシュ化するトークンの文字列
+///
+/// データベースに平文のトークンを保存しないよう、SHA-256 のハッシュ値を16進数の文字列で返します。
+/// トークンは十分にランダムなため、パスワードと異なりソルトは付けません。
+///
+/// 戻り値:
+/// - ハッシュ化されたトークンの文字列（64文字）
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
 /// パスワードをハッシュ化する関数
 ///
 /// `password` - ハッシュ化するパスワードの文字列
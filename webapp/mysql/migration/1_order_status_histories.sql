@@ -0,0 +1,13 @@
+-- 注文ステータスの遷移履歴を記録するテーブル
+CREATE TABLE IF NOT EXISTS order_status_histories (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    order_id INT NOT NULL,
+    from_status VARCHAR(50) NOT NULL,
+    to_status VARCHAR(50) NOT NULL,
+    changed_by INT NOT NULL,
+    changed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE,
+    FOREIGN KEY (changed_by) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_order_status_histories_order_id ON order_status_histories(order_id);
@@ -0,0 +1,4 @@
+-- 完了した注文のレッカー車が解放されると再び割り当てられるため、レッカー車IDの一意制約を外す
+-- 外部キー制約のためのインデックスを先に作成してから一意制約のインデックスを削除する
+CREATE INDEX idx_completed_orders_tow_truck_id ON completed_orders(tow_truck_id);
+ALTER TABLE completed_orders DROP INDEX tow_truck_id;
@@ -0,0 +1,10 @@
+-- 注文のキャンセル理由を記録するテーブル
+CREATE TABLE IF NOT EXISTS order_cancellations (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    order_id INT NOT NULL UNIQUE,
+    reason VARCHAR(50) NOT NULL,
+    cancelled_by INT NOT NULL,
+    cancelled_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE,
+    FOREIGN KEY (cancelled_by) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- セッションの有効期限を判定するため、作成時刻と最終利用時刻を記録する
+ALTER TABLE sessions
+    ADD COLUMN created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN last_seen_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+CREATE INDEX idx_sessions_created_at ON sessions(created_at);
+CREATE INDEX idx_sessions_last_seen_at ON sessions(last_seen_at);
@@ -0,0 +1,2 @@
+-- 同時に登録されたユーザー名の重複を防ぐため、ユーザー名に一意制約を付ける
+ALTER TABLE users ADD CONSTRAINT uq_users_username UNIQUE (username);
@@ -0,0 +1,27 @@
+-- レッカー車ごとの現在位置（locations は履歴として残す）
+CREATE TABLE IF NOT EXISTS current_locations (
+    tow_truck_id INT PRIMARY KEY,
+    node_id INT NOT NULL,
+    updated_at DATETIME NOT NULL,
+    FOREIGN KEY (tow_truck_id) REFERENCES tow_trucks(id) ON DELETE CASCADE
+);
+
+-- 既存の履歴から最新の位置を移す（同じ時刻の記録は後に追加されたものを優先する）
+-- 記録時刻のない履歴は最も古いものとして扱い、そのような履歴しかない場合は移行時の時刻（UTC）を記録する
+INSERT INTO current_locations (tow_truck_id, node_id, updated_at)
+SELECT tow_truck_id, node_id, COALESCE(timestamp, UTC_TIMESTAMP())
+FROM (
+    SELECT
+        tow_truck_id,
+        node_id,
+        timestamp,
+        ROW_NUMBER() OVER (
+            PARTITION BY tow_truck_id
+            ORDER BY timestamp IS NULL, timestamp DESC, id DESC
+        ) AS row_num
+    FROM locations
+) latest
+WHERE row_num = 1;
+
+-- レッカー車ごとに期間を指定して履歴を取得するためのインデックス
+CREATE INDEX idx_locations_tow_truck_id_timestamp ON locations(tow_truck_id, timestamp, id);
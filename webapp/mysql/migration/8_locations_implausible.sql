@@ -0,0 +1,2 @@
+-- 前回の位置から経過時間内にグラフ上で到達できない位置の更新を記録する
+ALTER TABLE locations ADD COLUMN implausible BOOLEAN NOT NULL DEFAULT FALSE;